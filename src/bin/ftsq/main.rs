@@ -0,0 +1,279 @@
+use fulltext_search_code_gen::executor::Executor;
+use fulltext_search_code_gen::vocabulary::Vocabulary;
+use fulltext_search_code_gen::{
+    compile_staged, lexer, parser, printer, Backend, CompileError, FieldType, Options,
+};
+use serde_json::json;
+use std::fs::{read_to_string, File};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::sync::Arc;
+
+mod repl;
+
+const USAGE: &str = "Usage: ftsq [OPTIONS] [QUERY]
+       ftsq fmt [--write] [FILE...]
+
+Compiles a full-text search query, reads the query from stdin if none is given.
+fmt formats queries over multiple lines instead, --write formats the files in place.
+
+Options:
+    --emit tokens|ast|sql|json  What to print, defaults to sql
+    --backend mssql             Backend to generate the query for
+    --execute                   Run the generated query using the executor
+    --no-keywords               Search for AND, OR and NOT instead of using them as operators
+    --normalize-weights         Scale any positive weights to add up to 1
+    --columns NAME,...          Columns a search can be restricted to, defaults to Title
+    --filters NAME:TYPE,...     Fields results can be filtered by, types are text, integer or date
+    --sort COLUMN[,asc|desc]    Sort the results by a column, rank breaks ties
+    --vocabulary FILE           Words fuzzy terms are expanded to, one per line
+    --lang LANGUAGE             Language for word breaking and stemming, e.g. de or 1031
+    --server NAME               MSSQL Server used by the executor
+    --format table|json         How to print the hits of an executed query
+    --repl                      Start an interactive session instead
+    -h, --help                  Print this message";
+
+// What the command line tool prints
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    Sql,
+    Json,
+}
+
+// How hits of an executed query are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Table,
+    Json,
+}
+
+// Command line arguments
+struct Args {
+    query: Option<String>,
+    emit: Emit,
+    format: Format,
+    execute: bool,
+    repl: bool,
+    options: Options,
+    executor: Executor,
+}
+
+// Main function of the command line tool
+// Prints errors to stderr and exits with a non-zero code
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let result = match args.first().map(String::as_str) {
+        Some("fmt") => run_fmt(&args[1..]),
+        _ => parse_args(args).and_then(run),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+// Parse the command line arguments
+// Input: arguments without the program name
+// Output: arguments or error for unknown or incomplete options
+fn parse_args(args: Vec<String>) -> std::io::Result<Args> {
+    let mut parsed = Args {
+        query: None,
+        emit: Emit::Sql,
+        format: Format::Table,
+        execute: false,
+        repl: false,
+        options: Options::default(),
+        executor: Executor::default(),
+    };
+    let mut query_parts: Vec<String> = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                parsed.emit = match expect_value(&arg, args.next())?.as_str() {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    "sql" => Emit::Sql,
+                    "json" => Emit::Json,
+                    other => return Err(invalid_input(format!("Unknown emit {}.", other))),
+                }
+            }
+            "--format" => {
+                parsed.format = match expect_value(&arg, args.next())?.as_str() {
+                    "table" => Format::Table,
+                    "json" => Format::Json,
+                    other => return Err(invalid_input(format!("Unknown format {}.", other))),
+                }
+            }
+            "--backend" => {
+                parsed.options.backend = expect_value(&arg, args.next())?
+                    .parse::<Backend>()
+                    .map_err(|error| invalid_input(error.to_string()))?
+            }
+            "--server" => parsed.executor.server = expect_value(&arg, args.next())?,
+            "--no-keywords" => parsed.options.keyword_operators = false,
+            "--normalize-weights" => parsed.options.normalize_weights = true,
+            "--filters" => {
+                parsed.options.filters = expect_value(&arg, args.next())?
+                    .split(',')
+                    .filter(|filter| !filter.trim().is_empty())
+                    .map(parse_filter)
+                    .collect::<std::io::Result<Vec<(String, FieldType)>>>()?
+            }
+            "--sort" => parsed
+                .options
+                .set_sort(&expect_value(&arg, args.next())?)
+                .map_err(|error| invalid_input(error.to_string()))?,
+            "--vocabulary" => {
+                let vocabulary = Vocabulary::load(&expect_value(&arg, args.next())?)?;
+                parsed.options.vocabulary = Some(Arc::new(vocabulary))
+            }
+            "--lang" => parsed.options.language = Some(expect_value(&arg, args.next())?),
+            "--columns" => {
+                parsed.options.columns = expect_value(&arg, args.next())?
+                    .split(',')
+                    .map(|column| column.trim().to_owned())
+                    .filter(|column| !column.is_empty())
+                    .collect()
+            }
+            "--execute" => parsed.execute = true,
+            "--repl" => parsed.repl = true,
+            _ if arg.starts_with("--") => {
+                return Err(invalid_input(format!("Unknown option {}.", arg)))
+            }
+            _ => query_parts.push(arg),
+        }
+    }
+    if !query_parts.is_empty() {
+        parsed.query = Some(query_parts.join(" "));
+    }
+    Ok(parsed)
+}
+
+// Filters are given as field name and type separated by a colon
+fn parse_filter(filter: &str) -> std::io::Result<(String, FieldType)> {
+    let (field, field_type) = filter
+        .split_once(':')
+        .ok_or_else(|| invalid_input(format!("Missing type of filter {}.", filter)))?;
+    let field_type = field_type
+        .trim()
+        .parse::<FieldType>()
+        .map_err(|error| invalid_input(error.to_string()))?;
+    Ok((field.trim().to_owned(), field_type))
+}
+
+// Options with values need the value as the next argument
+fn expect_value(option: &str, value: Option<String>) -> std::io::Result<String> {
+    value.ok_or_else(|| invalid_input(format!("Missing value for {}.", option)))
+}
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+// Compile the query, print what was asked for and execute it if requested
+fn run(args: Args) -> std::io::Result<()> {
+    if args.repl {
+        return repl::run(args.options);
+    }
+    let query = match args.query {
+        Some(query) => query,
+        None => {
+            let mut query = String::new();
+            std::io::stdin().read_to_string(&mut query)?;
+            query
+        }
+    };
+    // Tokens are printed even if the query cannot be parsed, unless it is executed
+    if args.emit == Emit::Tokens && !args.execute {
+        for token in lexer::lex_with_options(&query, &args.options) {
+            println!("{:?}", token);
+        }
+        return Ok(());
+    }
+    let compilation = compile_staged(&query, &args.options).map_err(|error| match error {
+        CompileError::Generate(error) => Error::new(ErrorKind::InvalidData, error.to_string()),
+        error => invalid_input(error.to_string()),
+    })?;
+    let tokens: Vec<_> = compilation.tokens.iter().map(|(token, _)| token).collect();
+    match args.emit {
+        Emit::Tokens => {
+            for token in &tokens {
+                println!("{:?}", token);
+            }
+        }
+        Emit::Ast => println!("{:#?}", compilation.ast),
+        Emit::Sql => println!("{}", compilation.sql),
+        Emit::Json => println!(
+            "{}",
+            json!({ "tokens": tokens, "ast": compilation.ast, "sql": compilation.sql })
+        ),
+    }
+    if args.execute {
+        let hits = args
+            .executor
+            .run(&compilation.sql)?
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Results cannot be read."))?;
+        print_hits(&hits, args.format);
+    }
+    Ok(())
+}
+
+// Formats queries over multiple lines
+// Input: arguments after 'fmt', '--write' formats files in place, no files reads from stdin
+// Output: formatted queries written to stdout or back to their files
+fn run_fmt(args: &[String]) -> std::io::Result<()> {
+    let write_back = args.iter().any(|arg| arg == "--write");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--write").collect();
+    if paths.is_empty() {
+        let mut query = String::new();
+        std::io::stdin().read_to_string(&mut query)?;
+        println!("{}", format_query(&query)?);
+    }
+    for path in paths {
+        let formatted = format_query(&read_to_string(path)?)?;
+        if write_back {
+            writeln!(File::create(path)?, "{}", formatted)?;
+        } else {
+            println!("{}", formatted);
+        }
+    }
+    Ok(())
+}
+
+// Parse a query and format it, parse errors are invalid input like in compilation
+fn format_query(query: &str) -> std::io::Result<String> {
+    parser::parse(lexer::lex(query))
+        .map(|ast| printer::format(&ast))
+        .map_err(|error| invalid_input(error.to_string()))
+}
+
+// Print hits of an executed query as aligned table or json array
+fn print_hits(hits: &[(String, u64)], format: Format) {
+    match format {
+        Format::Table => {
+            let width = hits
+                .iter()
+                .map(|(title, _)| title.chars().count())
+                .chain(std::iter::once("TITLE".len()))
+                .max()
+                .unwrap_or_default();
+            println!("{:<width$}  RANK", "TITLE", width = width);
+            for (title, rank) in hits {
+                println!("{:<width$}  {}", title, rank, width = width);
+            }
+        }
+        Format::Json => {
+            let hits: Vec<_> = hits
+                .iter()
+                .map(|(title, rank)| json!({ "title": title, "rank": rank }))
+                .collect();
+            println!("{}", serde_json::Value::Array(hits));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use fulltext_search_code_gen::{compile, compile_staged, printer, Backend, CompileError, Options};
+use std::fs::{read_to_string, OpenOptions};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+const PROMPT: &str = "ftsq> ";
+const HISTORY_FILE: &str = ".ftsq_history";
+
+const HELP: &str = "Enter a query to see the generated SQL.
+
+Commands:
+    :backend [NAME]   Show or set the backend
+    :limit [N]        Show or set the number of returned rows
+    :explain [QUERY]  Show tokens, ast and canonical form of the query or the last one
+    :history          List previous queries, rerun one with !N
+    :help             Print this message
+    :quit             Leave the REPL";
+
+// REPL state, history is kept in memory and appended to the history file
+struct Repl {
+    options: Options,
+    history: Vec<String>,
+    history_path: PathBuf,
+    color: bool,
+}
+
+// Main function of the REPL, reads queries line by line until EOF or :quit
+// Input: options the queries are compiled with
+pub fn run(options: Options) -> std::io::Result<()> {
+    let mut repl = Repl::new(options);
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("{}", PROMPT);
+        std::io::stdout().flush()?;
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        if !repl.eval(input)? {
+            return Ok(());
+        }
+    }
+}
+
+impl Repl {
+    // Initial REPL creation, loads the history of previous sessions
+    fn new(options: Options) -> Self {
+        let history_path = std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(HISTORY_FILE);
+        let history = read_to_string(&history_path)
+            .map(|contents| contents.lines().map(str::to_owned).collect())
+            .unwrap_or_default();
+        Self {
+            options,
+            history,
+            history_path,
+            color: std::io::stdout().is_terminal(),
+        }
+    }
+
+    // Evaluate one line of input
+    // Output: false if the REPL should stop
+    fn eval(&mut self, input: &str) -> std::io::Result<bool> {
+        if let Some(number) = input.strip_prefix('!') {
+            match number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| self.history.get(n))
+            {
+                Some(query) => {
+                    let query = query.clone();
+                    println!("{}", query);
+                    self.compile(&query)?;
+                }
+                None => self.error(&format!("No history entry {}.", number)),
+            }
+            return Ok(true);
+        }
+        if let Some(command) = input.strip_prefix(':') {
+            return Ok(self.command(command));
+        }
+        self.compile(input)?;
+        Ok(true)
+    }
+
+    // Meta commands change the options or inspect queries
+    // Output: false if the REPL should stop
+    fn command(&mut self, command: &str) -> bool {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+        match (name, argument) {
+            ("backend", "") => println!("{:?}", self.options.backend),
+            ("backend", backend) => match backend.parse::<Backend>() {
+                Ok(backend) => self.options.backend = backend,
+                Err(error) => self.error(&error.to_string()),
+            },
+            ("limit", "") => println!("{}", self.options.top_rows),
+            ("limit", limit) => match limit.parse::<u64>() {
+                Ok(limit) => self.options.top_rows = limit,
+                Err(_) => self.error(&format!("Invalid limit {}.", limit)),
+            },
+            ("explain", "") => match self.history.last() {
+                Some(last) => self.explain(last),
+                None => self.error("No query to explain."),
+            },
+            ("explain", query) => self.explain(query),
+            ("history", _) => {
+                for (number, query) in self.history.iter().enumerate() {
+                    println!("{:>4}  {}", number, query);
+                }
+            }
+            ("help", _) => println!("{}", HELP),
+            ("quit", _) | ("q", _) => return false,
+            _ => self.error(&format!("Unknown command :{}, see :help.", name)),
+        }
+        true
+    }
+
+    // Compile a query and print the generated SQL or the highlighted error
+    fn compile(&mut self, query: &str) -> std::io::Result<()> {
+        self.remember(query)?;
+        match compile(query, &self.options) {
+            Ok(sql) => println!("{}", sql),
+            Err(error) => self.report(query, &error),
+        }
+        Ok(())
+    }
+
+    // Show how a query is understood, step by step
+    fn explain(&self, query: &str) {
+        match compile_staged(query, &self.options) {
+            Ok(compilation) => {
+                println!("tokens:");
+                for (token, span) in &compilation.tokens {
+                    println!("    {:>3}..{:<3} {:?}", span.start, span.end, token);
+                }
+                println!("ast:\n{:#?}", compilation.ast);
+                println!("understood as:\n{}", printer::format(&compilation.ast));
+                println!("sql:\n{}", compilation.sql);
+            }
+            Err(error) => self.report(query, &error),
+        }
+    }
+
+    // Print a compile error, parse errors are printed below the query with the failing token underlined
+    fn report(&self, query: &str, error: &CompileError) {
+        match error {
+            CompileError::Parse { error, span } => {
+                let indent = " ".repeat(PROMPT.len());
+                let offset = query[..span.start].chars().count();
+                let width = query
+                    .get(span.clone())
+                    .map_or(1, |s| s.chars().count().max(1));
+                println!("{}{}", indent, query);
+                self.error(&format!(
+                    "{}{}{} {}",
+                    indent,
+                    " ".repeat(offset),
+                    "^".repeat(width),
+                    error
+                ));
+            }
+            error => self.error(&error.to_string()),
+        }
+    }
+
+    // Add a query to the history, consecutive duplicates are only stored once
+    fn remember(&mut self, query: &str) -> std::io::Result<()> {
+        if self.history.last().map(String::as_str) == Some(query) {
+            return Ok(());
+        }
+        self.history.push(query.to_owned());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_path)?;
+        writeln!(file, "{}", query)
+    }
+
+    // Print an error, in red if the output is a terminal
+    fn error(&self, message: &str) {
+        if self.color {
+            println!("\x1b[31m{}\x1b[0m", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
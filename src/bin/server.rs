@@ -0,0 +1,437 @@
+use actix_web::cookie::Cookie;
+use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer, Responder};
+use fulltext_search_code_gen::executor::{read_results, Executor};
+use fulltext_search_code_gen::history::{History, HistoryEntry};
+use fulltext_search_code_gen::saved::{SavedError, SavedSearches};
+use fulltext_search_code_gen::vocabulary::Vocabulary;
+use fulltext_search_code_gen::{compile_staged, completion, lexer, printer, CompileError, Options};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tera::{Context, Tera};
+
+// Words fuzzy terms are expanded to, fuzzy terms are rejected if the file does not exist
+const PATH_VOCABULARY: &str = "files\\vocabulary.txt";
+// Saved searches with their placeholders, created when the first search is saved
+const PATH_SAVED: &str = "files\\saved_searches.json";
+// Cookie with the id of the session whose search history is shown
+const SESSION_COOKIE: &str = "session";
+
+// Main function to start website on localhost:8080
+// Run using 'cargo watch -x run'
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let saved = SavedSearches::load(PATH_SAVED)
+        .map_err(|error| Error::new(ErrorKind::InvalidData, error.to_string()))?;
+    let saved = web::Data::new(Mutex::new(saved));
+    let history = web::Data::new(Mutex::new(History::default()));
+    // The vocabulary is loaded once, fuzzy terms are expanded to its words and terms completed from it
+    let vocabulary = Some(PATH_VOCABULARY)
+        .filter(|path| Path::new(path).exists())
+        .map(Vocabulary::load)
+        .transpose()?
+        .map(Arc::new);
+    let vocabulary = web::Data::new(vocabulary);
+    HttpServer::new(move || {
+        let tera = Tera::new("templates/**/*").unwrap();
+        App::new()
+            .data(tera)
+            .app_data(saved.clone())
+            .app_data(history.clone())
+            .app_data(vocabulary.clone())
+            .route("/", web::get().to(search))
+            .route("/", web::post().to(result))
+            .route("/saved", web::get().to(saved_list))
+            .route("/saved", web::post().to(saved_save))
+            .route("/saved/{name}/delete", web::post().to(saved_delete))
+            .route("/saved/{name}/run", web::post().to(saved_run))
+            .route("/api/saved", web::get().to(api_saved_list))
+            .route("/api/saved/{name}", web::get().to(api_saved_get))
+            .route("/api/saved/{name}", web::put().to(api_saved_save))
+            .route("/api/saved/{name}", web::delete().to(api_saved_delete))
+            .route("/api/saved/{name}/run", web::post().to(api_saved_run))
+            .route("/api/complete", web::get().to(api_complete))
+            .route("/api/highlight", web::get().to(api_highlight))
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await
+}
+
+// Code generator to translate an input to SQL
+// Input: search string and path to write result to
+// Output: SQL statement written to a file and the canonical form of the search string
+fn run_code_gen(search: String, options: &Options, path: &str) -> std::io::Result<String> {
+    match compile_staged(&search, options) {
+        // If compilation returns no error, write SQL statement to file
+        Ok(compilation) => {
+            write!(File::create(path)?, "{}", compilation.sql)?;
+            Ok(printer::print(&compilation.ast))
+        }
+        // Errors of the generator are invalid data, all others invalid input
+        Err(error @ CompileError::Generate(_)) => {
+            Err(Error::new(ErrorKind::InvalidData, error.to_string()))
+        }
+        Err(error) => Err(Error::new(ErrorKind::InvalidInput, error.to_string())),
+    }
+}
+
+// Search and Result structs to (de)serialize rust and website datatypes
+#[derive(Deserialize)]
+struct Search {
+    search: String,
+    lang: Option<String>,
+    sort: Option<String>,
+}
+#[derive(Serialize)]
+struct Result {
+    title: String,
+    rank: u64,
+    link: String,
+}
+
+// Saved search and run structs for the saved searches page and api
+#[derive(Deserialize)]
+struct SavedQuery {
+    #[serde(default)]
+    name: String,
+    query: String,
+}
+#[derive(Deserialize)]
+struct RunSaved {
+    #[serde(default)]
+    values: HashMap<String, String>,
+    lang: Option<String>,
+    sort: Option<String>,
+}
+#[derive(Serialize)]
+struct SavedResult {
+    search: String,
+    understood: String,
+    results: Option<Vec<Result>>,
+}
+#[derive(Deserialize)]
+struct Highlight {
+    q: String,
+}
+#[derive(Deserialize)]
+struct Complete {
+    q: String,
+    cursor: Option<usize>,
+}
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+}
+
+// Define functional parts of the search page
+// The search history of the session is listed below the search field
+async fn search(
+    tera: web::Data<Tera>,
+    history: web::Data<Mutex<History>>,
+    request: HttpRequest,
+) -> impl Responder {
+    let mut data = Context::new();
+    data.insert("title", "Search field");
+    let entries = request
+        .cookie(SESSION_COOKIE)
+        .map(|cookie| history.lock().unwrap().entries(cookie.value()))
+        .unwrap_or_default();
+    data.insert("history", &entries);
+    let rendered = tera.render("search.html", &data).unwrap();
+    HttpResponse::Ok().body(rendered)
+}
+
+// Define functional parts of the result page
+// The search is added to the history of the session, a new session is started if there is none
+async fn result(
+    tera: web::Data<Tera>,
+    history: web::Data<Mutex<History>>,
+    vocabulary: web::Data<Option<Arc<Vocabulary>>>,
+    request: HttpRequest,
+    data: web::Form<Search>,
+) -> impl Responder {
+    // Run code generator with the string from the search field
+    let outcome = run_search(&data, &vocabulary);
+    let mut history = history.lock().unwrap();
+    let mut response = HttpResponse::Ok();
+    let session = match request.cookie(SESSION_COOKIE) {
+        Some(cookie) if history.has_session(cookie.value()) => cookie.value().to_owned(),
+        _ => {
+            let session = history.start_session();
+            response.cookie(
+                Cookie::build(SESSION_COOKIE, session.clone())
+                    .path("/")
+                    .http_only(true)
+                    .finish(),
+            );
+            session
+        }
+    };
+    history.record(
+        &session,
+        HistoryEntry {
+            search: data.search.clone(),
+            lang: data.lang.clone(),
+            sort: data.sort.clone(),
+            results: outcome
+                .as_ref()
+                .ok()
+                .and_then(|(_, results)| results.as_ref().map(Vec::len)),
+            error: match &outcome {
+                Ok((_, Some(_))) => None,
+                Ok((_, None)) => Some("results cannot be read".to_owned()),
+                Err(error) => Some(error.to_string()),
+            },
+        },
+    );
+    response.body(render_result(&tera, &data, outcome))
+}
+
+// Render the result page of a search, errors are displayed instead of the results
+fn render_result(
+    tera: &Tera,
+    data: &Search,
+    outcome: std::io::Result<(String, Option<Vec<Result>>)>,
+) -> String {
+    let mut page_data = Context::new();
+    let mut results: Vec<Result> = Vec::new();
+    match outcome {
+        // If code generator returns no error display the results
+        Ok((understood, results_vec)) => {
+            page_data.insert("understood", &understood);
+            // Display search results if they could be read, otherwise diplay error
+            match results_vec {
+                Some(results_vec) => {
+                    results = results_vec;
+                    page_data.insert("title", "Results");
+                    page_data.insert("search", &data.search);
+                }
+                None => {
+                    page_data.insert("title", "Error");
+                    page_data.insert(
+                        "search",
+                        &format!("{} results cannot be read", &data.search),
+                    );
+                }
+            }
+        }
+        // If code generator returns error, display error instead of search results
+        Err(error) => {
+            page_data.insert("title", "Error");
+            page_data.insert(
+                "search",
+                &format!("{} threw an error: {}", &data.search, &error.to_string()),
+            );
+        }
+    }
+    page_data.insert("results", &results);
+    tera.render("result.html", &page_data).unwrap()
+}
+
+// Compile a search with the options of its fields and execute the SQL statement
+// Input: search with language and sort fields and the vocabulary loaded at startup
+// Output: canonical form of the search string and the results, none if they cannot be read
+fn run_search(
+    data: &Search,
+    vocabulary: &Option<Arc<Vocabulary>>,
+) -> std::io::Result<(String, Option<Vec<Result>>)> {
+    let executor = Executor::default();
+    // The language field selects the language for word breaking and stemming, empty uses the server default
+    let mut options = Options {
+        language: data.lang.clone().filter(|lang| !lang.trim().is_empty()),
+        vocabulary: vocabulary.clone(),
+        ..Options::default()
+    };
+    // The sort field is a column and optionally the order, only whitelisted columns are accepted
+    if let Some(sort) = data.sort.as_deref().filter(|sort| !sort.trim().is_empty()) {
+        options
+            .set_sort(sort)
+            .map_err(|error| Error::new(ErrorKind::InvalidInput, error.to_string()))?;
+    }
+    let understood = run_code_gen(data.search.clone(), &options, &executor.sql_path)?;
+    // Fit search results into Result struct to properly display them
+    let results = executor
+        .execute_sql()
+        .ok()
+        .and_then(|_| read_results(&executor.results_path))
+        .map(|results_vec| {
+            results_vec
+                .into_iter()
+                .map(|(title, rank)| Result {
+                    // link to the Wikipedia article is also provided, whitespaces need to be replaced
+                    link: title.replace(" ", "_"),
+                    title,
+                    rank,
+                })
+                .collect()
+        });
+    Ok((understood, results))
+}
+
+// Define functional parts of the saved searches page
+async fn saved_list(
+    tera: web::Data<Tera>,
+    saved: web::Data<Mutex<SavedSearches>>,
+) -> impl Responder {
+    render_saved(&tera, &saved.lock().unwrap(), None)
+}
+
+// Save a search from the form, the page shows the error if the query is invalid
+async fn saved_save(
+    tera: web::Data<Tera>,
+    saved: web::Data<Mutex<SavedSearches>>,
+    data: web::Form<SavedQuery>,
+) -> impl Responder {
+    let mut saved = saved.lock().unwrap();
+    let error = saved.save(&data.name, &data.query).err();
+    render_saved(&tera, &saved, error)
+}
+
+async fn saved_delete(
+    tera: web::Data<Tera>,
+    saved: web::Data<Mutex<SavedSearches>>,
+    name: web::Path<String>,
+) -> impl Responder {
+    let mut saved = saved.lock().unwrap();
+    let error = saved.delete(&name).err();
+    render_saved(&tera, &saved, error)
+}
+
+// Run a saved search, the form fields are the values of its parameters
+async fn saved_run(
+    tera: web::Data<Tera>,
+    saved: web::Data<Mutex<SavedSearches>>,
+    vocabulary: web::Data<Option<Arc<Vocabulary>>>,
+    name: web::Path<String>,
+    values: web::Form<HashMap<String, String>>,
+) -> impl Responder {
+    let bound = saved
+        .lock()
+        .unwrap()
+        .get(&name)
+        .and_then(|search| search.bind(&values));
+    match bound {
+        Ok(query) => {
+            let search = Search {
+                search: query,
+                lang: None,
+                sort: None,
+            };
+            let outcome = run_search(&search, &vocabulary);
+            HttpResponse::Ok().body(render_result(&tera, &search, outcome))
+        }
+        Err(error) => render_saved(&tera, &saved.lock().unwrap(), Some(error)),
+    }
+}
+
+fn render_saved(tera: &Tera, saved: &SavedSearches, error: Option<SavedError>) -> HttpResponse {
+    let mut page_data = Context::new();
+    page_data.insert("title", "Saved searches");
+    page_data.insert("saved", saved.list());
+    if let Some(error) = error {
+        page_data.insert("error", &error.to_string());
+    }
+    let rendered = tera.render("saved.html", &page_data).unwrap();
+    HttpResponse::Ok().body(rendered)
+}
+
+// Api to list, save, delete and run saved searches with json
+async fn api_saved_list(saved: web::Data<Mutex<SavedSearches>>) -> impl Responder {
+    HttpResponse::Ok().json(saved.lock().unwrap().list())
+}
+
+async fn api_saved_get(
+    saved: web::Data<Mutex<SavedSearches>>,
+    name: web::Path<String>,
+) -> impl Responder {
+    match saved.lock().unwrap().get(&name) {
+        Ok(search) => HttpResponse::Ok().json(search),
+        Err(error) => api_error(error),
+    }
+}
+
+// The name is taken from the path, a name in the body is ignored
+async fn api_saved_save(
+    saved: web::Data<Mutex<SavedSearches>>,
+    name: web::Path<String>,
+    data: web::Json<SavedQuery>,
+) -> impl Responder {
+    match saved.lock().unwrap().save(&name, &data.query) {
+        Ok(search) => HttpResponse::Ok().json(search),
+        Err(error) => api_error(error),
+    }
+}
+
+async fn api_saved_delete(
+    saved: web::Data<Mutex<SavedSearches>>,
+    name: web::Path<String>,
+) -> impl Responder {
+    match saved.lock().unwrap().delete(&name) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(error) => api_error(error),
+    }
+}
+
+// Run a saved search with bound values and the usual language and sort options
+async fn api_saved_run(
+    saved: web::Data<Mutex<SavedSearches>>,
+    vocabulary: web::Data<Option<Arc<Vocabulary>>>,
+    name: web::Path<String>,
+    data: web::Json<RunSaved>,
+) -> impl Responder {
+    let bound = saved
+        .lock()
+        .unwrap()
+        .get(&name)
+        .and_then(|search| search.bind(&data.values));
+    let search = match bound {
+        Ok(query) => Search {
+            search: query,
+            lang: data.lang.clone(),
+            sort: data.sort.clone(),
+        },
+        Err(error) => return api_error(error),
+    };
+    match run_search(&search, &vocabulary) {
+        Ok((understood, results)) => HttpResponse::Ok().json(SavedResult {
+            search: search.search,
+            understood,
+            results,
+        }),
+        Err(error) => HttpResponse::BadRequest().json(ApiError {
+            error: error.to_string(),
+        }),
+    }
+}
+
+// Suggest what can come next at the cursor, the cursor is a character position and the end by default
+async fn api_complete(
+    vocabulary: web::Data<Option<Arc<Vocabulary>>>,
+    data: web::Query<Complete>,
+) -> impl Responder {
+    let cursor = data.cursor.unwrap_or_else(|| data.q.chars().count());
+    let suggestions =
+        completion::complete(&data.q, cursor, &Options::default(), vocabulary.as_deref());
+    HttpResponse::Ok().json(suggestions)
+}
+
+// Token stream of a query with the semantic class of every token, used to colour queries
+async fn api_highlight(data: web::Query<Highlight>) -> impl Responder {
+    HttpResponse::Ok().json(lexer::highlight(&data.q, &Options::default()))
+}
+
+// Unknown searches are not found, failing to write the store is an error of the server
+fn api_error(error: SavedError) -> HttpResponse {
+    let mut response = match error {
+        SavedError::UnknownSearch(_) => HttpResponse::NotFound(),
+        SavedError::Io(_) | SavedError::Json(_) => HttpResponse::InternalServerError(),
+        _ => HttpResponse::BadRequest(),
+    };
+    response.json(ApiError {
+        error: error.to_string(),
+    })
+}
@@ -1,6 +1,9 @@
+use serde::Serialize;
+
 use crate::code_gen::lexer::Token;
+use crate::code_gen::options::Order;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Statement {
     Group {
         expression: Expression,
@@ -10,6 +13,10 @@ pub enum Statement {
         operator: Operator,
         second_statement: Box<Statement>,
     },
+    Prefix {
+        operator: Operator,
+        statement: Box<Statement>,
+    },
     Contains {
         expression: Expression,
     },
@@ -25,14 +32,51 @@ pub enum Statement {
     Near {
         parameter: Vec<Expression>,
         proximity: Expression,
+        ordered: bool,
     },
     Weighted {
-        parameter: Vec<(Expression, Expression)>,
+        parameter: Vec<(Statement, Expression)>,
+    },
+    Freetext {
+        text: String,
+        language: Option<Expression>,
+    },
+    Column {
+        columns: Vec<String>,
+        statement: Box<Statement>,
+    },
+    Language {
+        language: Expression,
+    },
+    Filter {
+        field: String,
+        comparison: Comparison,
+        value: FilterValue,
+    },
+    Range {
+        field: String,
+        from: FilterValue,
+        to: FilterValue,
+    },
+    Sort {
+        column: String,
+        order: Order,
+    },
+    Fuzzy {
+        term: Expression,
+        distance: u64,
+    },
+    Define {
+        name: String,
+        statement: Box<Statement>,
+    },
+    Macro {
+        name: String,
     },
     EoF,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Expression {
     WordOrPhrase(String),
     Number(u64),
@@ -41,7 +85,24 @@ pub enum Expression {
     Prefix(Operator, Box<Expression>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FilterValue {
+    Text(String),
+    Integer(u64),
+    Date(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum Operator {
     And,
     Or,
@@ -0,0 +1,384 @@
+use serde::Serialize;
+
+use crate::code_gen::lexer::{self, Token, TokenClass};
+use crate::code_gen::options::Options;
+use crate::code_gen::vocabulary::Vocabulary;
+
+// Maximal number of suggested terms
+const MAX_TERMS: usize = 10;
+// Functions which can be nested as parameters
+const NEAR_FUNCTIONS: [&str; 2] = ["@contains", "@startswith"];
+const WEIGHTED_FUNCTIONS: [&str; 5] = [
+    "@contains",
+    "@startswith",
+    "@inflection",
+    "@thesaurus",
+    "@near",
+];
+
+// Kinds of suggestions, serialized in lowercase
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionKind {
+    Function,
+    Colon,
+    Comma,
+    Operator,
+    Term,
+    Column,
+}
+
+// Suggestion which replaces the characters from start to end, both are character positions in the query
+// Start and end are the cursor if the suggestion is inserted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub text: String,
+    pub kind: SuggestionKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Main function to suggest what can come next at the cursor
+// Only the query before the cursor is read, the tokens are scanned instead of parsed so unfinished queries work
+// Input: query, cursor as character position, options and vocabulary to complete terms with
+// Output: vec of suggestions, empty if nothing fits
+pub fn complete(
+    query: &str,
+    cursor: usize,
+    options: &Options,
+    vocabulary: Option<&Vocabulary>,
+) -> Vec<Suggestion> {
+    let cursor_byte = query
+        .char_indices()
+        .nth(cursor)
+        .map(|(byte, _)| byte)
+        .unwrap_or(query.len());
+    let before = &query[..cursor_byte];
+    let cursor = before.chars().count();
+    let (tokens, spans): (Vec<Token>, Vec<_>) =
+        lexer::lex_spanned(before, options).into_iter().unzip();
+    let insert = |text: &str, kind: SuggestionKind| Suggestion {
+        text: text.to_owned(),
+        kind,
+        start: cursor,
+        end: cursor,
+    };
+    // The last token is still being typed if it ends at the cursor
+    let typed = spans
+        .last()
+        .filter(|span| span.end == before.len())
+        .map(|span| (&before[span.clone()], before[..span.start].chars().count()));
+    match typed {
+        // Function names are completed after @ until the colon is written
+        Some((slice, start)) if slice.starts_with('@') => lexer::complete_function(slice)
+            .into_iter()
+            .map(|name| Suggestion {
+                text: format!("{}:", name),
+                kind: SuggestionKind::Function,
+                start,
+                end: cursor,
+            })
+            .collect(),
+        // Columns of @in are completed from the configured columns
+        Some((slice, start))
+            if matches!(tokens.last(), Some(Token::WordOrPhrase(_)))
+                && scan(&tokens[..tokens.len() - 1]).0.columns =>
+        {
+            let mut suggestions: Vec<Suggestion> = options
+                .columns
+                .iter()
+                .filter(|column| {
+                    column.to_lowercase().starts_with(&slice.to_lowercase())
+                        && !column.eq_ignore_ascii_case(slice)
+                })
+                .map(|column| Suggestion {
+                    text: column.clone(),
+                    kind: SuggestionKind::Column,
+                    start,
+                    end: cursor,
+                })
+                .collect();
+            suggestions.extend(following(&tokens, options, &insert));
+            suggestions
+        }
+        // Words are completed from the vocabulary, what can follow the word is suggested aswell
+        // Phrases are not completed
+        Some((slice, start))
+            if matches!(tokens.last(), Some(Token::WordOrPhrase(_))) && !slice.starts_with('"') =>
+        {
+            let mut suggestions: Vec<Suggestion> = vocabulary
+                .map(|vocabulary| vocabulary.starting_with(slice))
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|word| *word != slice.to_lowercase())
+                .take(MAX_TERMS)
+                .map(|word| Suggestion {
+                    text: word,
+                    kind: SuggestionKind::Term,
+                    start,
+                    end: cursor,
+                })
+                .collect();
+            suggestions.extend(following(&tokens, options, &insert));
+            suggestions
+        }
+        _ => following(&tokens, options, &insert),
+    }
+}
+
+// Innermost part of the query whose end is not written yet
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Scope<'t> {
+    // Function whose parameters are open, none outside of all functions
+    function: Option<&'t Token>,
+    // Open parentheses inside the scope
+    depth: usize,
+    // Number of parameters before the current one, parameters are separated by commas
+    parameter: usize,
+    // The first colon of @in closes its columns, the second one closes the statement after them
+    columns: bool,
+}
+
+// Scan the tokens for the open scopes, the colon after a function opens its parameters and the next colon closes them
+// Input: tokens before the cursor
+// Output: innermost scope and whether the last token closed a function
+fn scan(tokens: &[Token]) -> (Scope<'_>, bool) {
+    let mut scopes: Vec<Scope> = vec![Scope::default()];
+    let mut closed = false;
+    for (i, token) in tokens.iter().enumerate() {
+        closed = false;
+        let scope = scopes.last_mut().unwrap();
+        match token {
+            Token::Colon if i > 0 && is_function(&tokens[i - 1]) => scopes.push(Scope {
+                function: Some(&tokens[i - 1]),
+                columns: tokens[i - 1] == Token::In,
+                ..Scope::default()
+            }),
+            Token::Colon if scope.columns => scope.columns = false,
+            Token::Colon if scope.function.is_some() => {
+                scopes.pop();
+                closed = true;
+            }
+            Token::Comma if scope.depth == 0 => scope.parameter += 1,
+            Token::LeftParen => scope.depth += 1,
+            Token::RightParen => scope.depth = scope.depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    (scopes.pop().unwrap_or_default(), closed)
+}
+
+// Suggest the tokens which can follow the tokens before the cursor
+// Input: tokens, options and function to create an inserted suggestion
+// Output: vec of suggestions
+fn following(
+    tokens: &[Token],
+    options: &Options,
+    insert: &dyn Fn(&str, SuggestionKind) -> Suggestion,
+) -> Vec<Suggestion> {
+    let (scope, closed) = scan(tokens);
+    let last = match tokens.last() {
+        Some(last) => last,
+        None => return Vec::new(),
+    };
+    let functions = |names: &[&str]| -> Vec<Suggestion> {
+        names
+            .iter()
+            .map(|name| insert(&format!("{}:", name), SuggestionKind::Function))
+            .collect()
+    };
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    if is_function(last) {
+        suggestions.push(insert(":", SuggestionKind::Colon));
+    } else if is_term(last) || closed || *last == Token::RightParen {
+        // A term or parameter is complete, so it can be combined, closed or continued
+        match scope.function {
+            _ if scope.columns => {
+                suggestions.push(insert(":", SuggestionKind::Colon));
+                suggestions.push(insert(",", SuggestionKind::Comma));
+            }
+            None
+            | Some(
+                Token::In | Token::Contains | Token::Starts | Token::Inflection | Token::Thesaurus,
+            ) => {
+                suggestions.push(insert("&", SuggestionKind::Operator));
+                suggestions.push(insert("|", SuggestionKind::Operator));
+                if scope.depth > 0 {
+                    suggestions.push(insert(")", SuggestionKind::Operator));
+                } else if scope.function.is_some() {
+                    suggestions.push(insert(":", SuggestionKind::Colon));
+                }
+            }
+            Some(function) => {
+                suggestions.push(insert(":", SuggestionKind::Colon));
+                if matches!(
+                    function,
+                    Token::Near
+                        | Token::Weighted
+                        | Token::Range
+                        | Token::Fuzzy
+                        | Token::Freetext
+                        | Token::Sort
+                ) {
+                    suggestions.push(insert(",", SuggestionKind::Comma));
+                }
+            }
+        }
+    } else if is_operand_expected(last) {
+        // Only what can start the current parameter or statement is suggested
+        match scope.function {
+            _ if scope.columns => suggestions.extend(
+                options
+                    .columns
+                    .iter()
+                    .map(|column| insert(column, SuggestionKind::Column)),
+            ),
+            None | Some(Token::In) => {
+                suggestions.extend(functions(&lexer::complete_function("@")));
+                suggestions.push(insert("(", SuggestionKind::Operator));
+            }
+            Some(Token::Contains | Token::Starts | Token::Inflection | Token::Thesaurus) => {
+                suggestions.push(insert("(", SuggestionKind::Operator))
+            }
+            Some(Token::Near) => suggestions.extend(functions(&NEAR_FUNCTIONS)),
+            // Weighted terms and their weights alternate
+            Some(Token::Weighted) if scope.parameter % 2 == 0 => {
+                suggestions.extend(functions(&WEIGHTED_FUNCTIONS))
+            }
+            _ => {}
+        }
+    }
+    suggestions
+}
+
+fn is_function(token: &Token) -> bool {
+    token.class() == TokenClass::Function
+}
+
+// Tokens after which a term, parameter or statement has to follow
+// Keyword operators are lexed as operators, so AND, OR and NOT are covered aswell
+fn is_operand_expected(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Colon
+            | Token::Comma
+            | Token::And
+            | Token::Plus
+            | Token::Or
+            | Token::Bang
+            | Token::Minus
+            | Token::Tilde
+            | Token::LeftParen
+            | Token::Assign
+            | Token::Semicolon
+    )
+}
+
+// Tokens which can end a term or parameter
+fn is_term(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::WordOrPhrase(_)
+            | Token::Number(_)
+            | Token::ZeroToOne(_)
+            | Token::Date(_)
+            | Token::Macro(_)
+            | Token::RightBracket
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Texts of the suggestions with the cursor at the end of the query
+    fn texts(query: &str, vocabulary: Option<&Vocabulary>) -> Vec<String> {
+        complete(
+            query,
+            query.chars().count(),
+            &Options::default(),
+            vocabulary,
+        )
+        .into_iter()
+        .map(|suggestion| suggestion.text)
+        .collect()
+    }
+
+    #[test]
+    fn completes_function_names() {
+        let suggestions = complete("@ne & rust", 3, &Options::default(), None);
+        assert_eq!(
+            suggestions,
+            vec![Suggestion {
+                text: String::from("@near:"),
+                kind: SuggestionKind::Function,
+                start: 0,
+                end: 3,
+            }]
+        );
+        assert_eq!(texts("@near ", None), vec![":"]);
+    }
+
+    #[test]
+    fn completes_terms_from_vocabulary() {
+        let vocabulary = Vocabulary::new(vec!["rust".into(), "Rusty".into(), "go".into()]);
+        assert_eq!(
+            texts("ru", Some(&vocabulary)),
+            vec!["rust", "rusty", "&", "|"]
+        );
+        // Phrases are not completed
+        assert_eq!(texts("\"ru\"", Some(&vocabulary)), vec!["&", "|"]);
+    }
+
+    #[test]
+    fn suggests_what_starts_a_statement() {
+        for query in ["rust AND", "rust AND ", "rust & ", "!", "(", "$x = "] {
+            let suggestions = texts(query, None);
+            assert!(
+                suggestions.contains(&String::from("@contains:")),
+                "{}",
+                query
+            );
+            assert!(suggestions.contains(&String::from("(")), "{}", query);
+        }
+        assert!(texts("", None).is_empty());
+    }
+
+    #[test]
+    fn suggests_nested_functions_as_parameters() {
+        assert_eq!(texts("@near:", None), vec!["@contains:", "@startswith:"]);
+        assert_eq!(
+            texts("@near:rust,", None),
+            vec!["@contains:", "@startswith:"]
+        );
+        assert_eq!(texts("@weighted:", None).len(), 5);
+        // Weights are numbers
+        assert!(texts("@weighted:rust,", None).is_empty());
+        assert_eq!(texts("@weighted:rust,0.5,", None).len(), 5);
+    }
+
+    #[test]
+    fn suggests_operators_inside_expression_parameters() {
+        assert_eq!(texts("@contains:ru", None), vec!["&", "|", ":"]);
+        assert_eq!(texts("@contains:(ru", None), vec!["&", "|", ")"]);
+        assert_eq!(texts("@contains:(ru)", None), vec!["&", "|", ":"]);
+        assert_eq!(texts("@contains:ru:", None), vec!["&", "|"]);
+    }
+
+    #[test]
+    fn suggests_commas_inside_parameter_lists() {
+        assert_eq!(texts("@near:rust", None), vec![":", ","]);
+        assert_eq!(texts("@near:@startswith:ru:", None), vec![":", ","]);
+        assert_eq!(texts("@fuzzy:rust", None), vec![":", ","]);
+        assert_eq!(texts("@where:size>5", None), vec![":"]);
+    }
+
+    #[test]
+    fn keeps_in_open_after_its_columns() {
+        assert_eq!(texts("@in:", None), vec!["Title"]);
+        assert_eq!(texts("@in:ti", None), vec!["Title", ":", ","]);
+        assert_eq!(texts("@in:title:ru", None), vec!["&", "|", ":"]);
+        assert!(texts("@in:title:", None).contains(&String::from("@near:")));
+        assert_eq!(texts("@in:title:ru:", None), vec!["&", "|"]);
+    }
+}
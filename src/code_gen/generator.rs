@@ -1,33 +1,450 @@
 use std::slice::Iter;
 use thiserror::Error;
 
-use crate::code_gen::ast::{Expression, Operator, Statement};
+use crate::code_gen::ast::{Comparison, Expression, FilterValue, Operator, Statement};
+use crate::code_gen::options::{Options, Order, RANK};
 
-// Database constants
-const DB_NAME: &str = "Wikipedia";
-const TBL_NAME: &str = "[dbo].[Article]";
-const RETURN_ATTRIBUTE: &str = "Title";
-const TOP_ROWS: u64 = 5;
+// Words with a special meaning inside a CONTAINS search condition
+const SQL_KEYWORDS: [&str; 4] = ["AND", "OR", "NOT", "NEAR"];
+// Language codes and the locale identifiers MSSQL selects word breakers, stemmers and thesaurus files by
+const LANGUAGE_CODES: [(&str, u64); 11] = [
+    ("de", 1031),
+    ("en", 1033),
+    ("es", 3082),
+    ("fr", 1036),
+    ("it", 1040),
+    ("ja", 1041),
+    ("nl", 1043),
+    ("pt", 2070),
+    ("ru", 1049),
+    ("sv", 1053),
+    ("zh", 2052),
+];
 
 // Main function to start the generation process
-// Input: vec of statements (ast)
+// Input: vec of statements (ast) and options describing the database
 // Output: string (sql statement)
-pub fn generate(ast: Vec<Statement>) -> Result<String, GenerateError> {
-    let mut generator = Generator::new(ast.iter());
+pub fn generate(ast: Vec<Statement>, options: &Options) -> Result<String, GenerateError> {
+    // Language and sort options apply to the whole query and are no part of the search condition
+    let (query_options, ast): (Vec<Statement>, Vec<Statement>) =
+        ast.into_iter().partition(|statement| {
+            matches!(
+                statement,
+                Statement::Language { .. } | Statement::Sort { .. }
+            )
+        });
+    let (languages, sorts): (Vec<Statement>, Vec<Statement>) = query_options
+        .into_iter()
+        .partition(|statement| matches!(statement, Statement::Language { .. }));
+    let mut sort: Vec<(String, Order)> = sorts
+        .into_iter()
+        .filter_map(|statement| match statement {
+            Statement::Sort { column, order } => Some((column, order)),
+            _ => None,
+        })
+        .collect();
+    if sort.is_empty() {
+        sort.extend(options.sort.clone());
+    }
+    // Sort columns are written into the SQL, so they are checked again in case the options were set directly
+    if let Some((column, _)) = sort
+        .iter()
+        .find(|(column, _)| options.sort_column(column).as_ref() != Some(column))
+    {
+        return Err(GenerateError::UnknownSortColumn(column.clone()));
+    }
+    let (returned, sort_keys, order_by) = generate_sort(&sort, options);
+    let mut query_language = options.language.clone().map(|language| {
+        language
+            .parse::<u64>()
+            .map(Expression::Number)
+            .unwrap_or(Expression::WordOrPhrase(language))
+    });
+    if let Some(Statement::Language { language }) = languages.first() {
+        if languages.iter().any(|other| other != &languages[0]) {
+            return Err(GenerateError::MultipleLanguages);
+        }
+        query_language = Some(language.clone());
+    }
+    // Statements separated by a language or sort option are searched together
+    // Filters are no part of the search condition, they restrict the results of the table function
+    let mut filters: Vec<Statement> = Vec::new();
+    let ast: Vec<Statement> = match ast
+        .into_iter()
+        .filter(|statement| *statement != Statement::EoF)
+        .reduce(|statement, second_statement| Statement::Infix {
+            statement: Box::new(statement),
+            operator: Operator::And,
+            second_statement: Box::new(second_statement),
+        }) {
+        Some(statement) => split_filters(statement, &mut filters)?
+            .into_iter()
+            .collect(),
+        None => Vec::new(),
+    };
+    if ast.is_empty() && !filters.is_empty() {
+        return Err(GenerateError::MissingSearchCondition);
+    }
+    let (declarations, predicates) = generate_filters(&filters);
+    // Functions searching different columns are split into parts, every part is searched with its own table function
+    let mut parts: Vec<(Vec<String>, Statement)> = Vec::new();
+    let condition = match ast.first() {
+        Some(statement) => split_scopes(statement, &[], &mut parts)?,
+        None => String::new(),
+    };
+    let (rank, joins, restriction) = if parts.len() > 1 {
+        let language = generate_language(query_language);
+        let mut joins: Vec<String> = Vec::new();
+        let mut ranks: Vec<String> = Vec::new();
+        for (index, (columns, statement)) in parts.into_iter().enumerate() {
+            let alias = format!("KEY_TBL{}", index);
+            let search_parts = generate_search_parts(&[statement])?;
+            joins.push(generate_join(
+                "LEFT",
+                "CONTAINSTABLE",
+                &options.table,
+                columns,
+                &search_parts,
+                &language,
+                &alias,
+            ));
+            ranks.push(format!("ISNULL({}.RANK, 0)", alias));
+        }
+        // Rows are found by the condition on the parts, their rank is the sum of the ranks of the parts
+        let rank = format!("({})", ranks.join(" + "));
+        (
+            format!("{} AS RANK", rank),
+            joins.join(" "),
+            format!("{} AND {} > 5", condition, rank),
+        )
+    } else {
+        let columns = parts.pop().map(|(columns, _)| columns).unwrap_or_default();
+        // A query of a single freetext function searches with FREETEXTTABLE, any other with CONTAINSTABLE
+        let (table_function, search_parts, language) = match ast
+            .iter()
+            .map(unscoped)
+            .collect::<Vec<&Statement>>()
+            .as_slice()
+        {
+            [Statement::Freetext { text, language }] => (
+                "FREETEXTTABLE",
+                vec![text.clone()],
+                generate_language(language.clone().or(query_language)),
+            ),
+            _ => (
+                "CONTAINSTABLE",
+                generate_search_parts(&ast)?,
+                generate_language(query_language),
+            ),
+        };
+        (
+            String::from("KEY_TBL.RANK"),
+            generate_join(
+                "INNER",
+                table_function,
+                &options.table,
+                columns,
+                &search_parts,
+                &language,
+                "KEY_TBL",
+            ),
+            String::from("KEY_TBL.RANK > 5"),
+        )
+    };
+    Ok(format!(
+        "USE {};{} SELECT TOP {} {} FROM(SELECT FT_TBL.{},{} {} FROM {} AS FT_TBL {} WHERE {}{}) AS FS_RESULT ORDER BY {};",
+        options.db_name,
+        declarations,
+        options.top_rows,
+        returned,
+        options.return_attribute,
+        sort_keys,
+        rank,
+        options.table,
+        joins,
+        restriction,
+        predicates,
+        order_by
+    ))
+}
+
+// Generate the search conditions of all statements
+// Input: statements to generate
+// Output: vec of strings, one for each statement
+fn generate_search_parts(statements: &[Statement]) -> Result<Vec<String>, GenerateError> {
+    let mut generator = Generator::new(statements.iter());
     // write twice to overwrite initial EoF statements
     generator.write();
     generator.write();
-    let mut sql_parts: Vec<String> = Vec::new();
-    sql_parts.push(format!(
-        "USE {}; SELECT TOP {} * FROM(SELECT FT_TBL.{}, KEY_TBL.RANK FROM {} AS FT_TBL INNER JOIN CONTAINSTABLE({}, *, '",
-        DB_NAME, TOP_ROWS, RETURN_ATTRIBUTE, TBL_NAME, TBL_NAME
-    ));
+    let mut search_parts: Vec<String> = Vec::new();
     // generate all functions as JOIN constraints
     while let Some(sql_part) = generator.next()? {
-        sql_parts.push(sql_part);
+        search_parts.push(sql_part);
+    }
+    Ok(search_parts)
+}
+
+// Generate a full-text table function joined to the searched table by its key
+// Input: kind of join, table function, table, columns to search, search condition, language argument and alias
+// Output: string
+fn generate_join(
+    join: &str,
+    table_function: &str,
+    table: &str,
+    columns: Vec<String>,
+    search_parts: &[String],
+    language: &str,
+    alias: &str,
+) -> String {
+    format!(
+        "{} JOIN {}({}, {}, ' {} '{}) AS {} ON FT_TBL.[ID] = {}.[KEY]",
+        join,
+        table_function,
+        table,
+        generate_columns(columns),
+        // Single quotes are escaped as the search condition is a string literal
+        search_parts.join(" ").replace('\'', "''"),
+        language,
+        alias,
+        alias
+    )
+}
+
+// Generate the sorting of the results, rank is added as the last tiebreaker
+// Columns other than rank and the returned attribute are selected as additional sort keys, which are not returned
+// Input: sort columns and their order
+// Output: returned columns, additional selected columns and the ORDER BY clause
+fn generate_sort(sort: &[(String, Order)], options: &Options) -> (String, String, String) {
+    let mut sort_keys = String::new();
+    let mut order_by: Vec<String> = Vec::new();
+    for (index, (column, order)) in sort.iter().enumerate() {
+        let key = if column == RANK {
+            String::from("FS_RESULT.RANK")
+        } else if column.eq_ignore_ascii_case(&options.return_attribute) {
+            format!("FS_RESULT.{}", options.return_attribute)
+        } else {
+            sort_keys.push_str(&format!(" FT_TBL.[{}] AS SORT_KEY{},", column, index));
+            format!("FS_RESULT.SORT_KEY{}", index)
+        };
+        let direction = match order {
+            Order::Ascending => "ASC",
+            Order::Descending => "DESC",
+        };
+        order_by.push(format!("{} {}", key, direction));
+    }
+    if !sort.iter().any(|(column, _)| column == RANK) {
+        order_by.push(String::from("FS_RESULT.RANK DESC"));
+    }
+    let returned = if sort_keys.is_empty() {
+        String::from("*")
+    } else {
+        format!("FS_RESULT.{}, FS_RESULT.RANK", options.return_attribute)
+    };
+    (returned, sort_keys, order_by.join(", "))
+}
+
+// Generate the optional language argument of a full-text table function
+// Languages codes are replaced by their locale identifier, other names are passed on as language alias
+// Input: language id, code or name
+// Output: string, empty if no language is given
+fn generate_language(language: Option<Expression>) -> String {
+    match language {
+        Some(Expression::Number(u)) => format!(", LANGUAGE {}", u),
+        Some(Expression::WordOrPhrase(s)) => {
+            let name = s.trim_matches('"');
+            match LANGUAGE_CODES
+                .iter()
+                .find(|(code, _)| code.eq_ignore_ascii_case(name))
+            {
+                Some((_, id)) => format!(", LANGUAGE {}", id),
+                None => format!(", LANGUAGE '{}'", name.replace('\'', "''")),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+// Generate the filters as predicates on the full-text table, the values are passed as typed variables
+// Input: filter and range statements
+// Output: variable declarations and predicates, both empty if there are no filters
+fn generate_filters(filters: &[Statement]) -> (String, String) {
+    let mut declarations: Vec<String> = Vec::new();
+    let mut predicates = String::new();
+    for filter in filters {
+        match filter {
+            Statement::Filter {
+                field,
+                comparison,
+                value,
+            } => {
+                let sql_comparison = match comparison {
+                    Comparison::Equal => "=",
+                    Comparison::NotEqual => "<>",
+                    Comparison::Less => "<",
+                    Comparison::LessEqual => "<=",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterEqual => ">=",
+                };
+                let variable = declare(&mut declarations, value);
+                predicates.push_str(&format!(
+                    " AND FT_TBL.[{}] {} {}",
+                    field, sql_comparison, variable
+                ));
+            }
+            Statement::Range { field, from, to } => {
+                let from = declare(&mut declarations, from);
+                let to = declare(&mut declarations, to);
+                predicates.push_str(&format!(
+                    " AND FT_TBL.[{}] BETWEEN {} AND {}",
+                    field, from, to
+                ));
+            }
+            _ => (),
+        }
+    }
+    (declarations.concat(), predicates)
+}
+
+// Declare a variable for a filter value
+// Input: declarations so far and the value
+// Output: name of the variable
+fn declare(declarations: &mut Vec<String>, value: &FilterValue) -> String {
+    let (sql_type, literal) = match value {
+        FilterValue::Text(s) => ("NVARCHAR(4000)", format!("N'{}'", s.replace('\'', "''"))),
+        FilterValue::Integer(u) => ("BIGINT", u.to_string()),
+        FilterValue::Date(date) => ("DATE", format!("'{}'", date)),
+    };
+    let variable = format!("@filter{}", declarations.len());
+    declarations.push(format!(" DECLARE {} {} = {};", variable, sql_type, literal));
+    variable
+}
+
+// Move the filters of a statement into a list, filters can only be combined with the search condition by AND
+// Input: statement and the filters found so far
+// Output: statement without filters, none if it only consists of filters
+fn split_filters(
+    statement: Statement,
+    filters: &mut Vec<Statement>,
+) -> Result<Option<Statement>, GenerateError> {
+    match statement {
+        Statement::Filter { .. } | Statement::Range { .. } => {
+            filters.push(statement);
+            Ok(None)
+        }
+        Statement::Infix {
+            statement,
+            operator: Operator::And,
+            second_statement,
+        } => {
+            let first = split_filters(*statement, filters)?;
+            let second = split_filters(*second_statement, filters)?;
+            Ok(match (first, second) {
+                (Some(first), Some(second)) => Some(Statement::Infix {
+                    statement: Box::new(first),
+                    operator: Operator::And,
+                    second_statement: Box::new(second),
+                }),
+                (first, second) => first.or(second),
+            })
+        }
+        statement if has_filter(&statement) => Err(GenerateError::MisplacedFilter(statement)),
+        statement => Ok(Some(statement)),
+    }
+}
+
+// Whether a statement contains a filter
+fn has_filter(statement: &Statement) -> bool {
+    match statement {
+        Statement::Filter { .. } | Statement::Range { .. } => true,
+        Statement::Infix {
+            statement,
+            second_statement,
+            ..
+        } => has_filter(statement) || has_filter(second_statement),
+        Statement::Prefix { statement, .. } | Statement::Column { statement, .. } => {
+            has_filter(statement)
+        }
+        _ => false,
+    }
+}
+
+// Generate the column argument of a full-text table function
+// Input: columns to search
+// Output: string, * if all columns are searched
+fn generate_columns(columns: Vec<String>) -> String {
+    if columns.is_empty() {
+        String::from("*")
+    } else {
+        format!("({})", columns.join(", "))
+    }
+}
+
+// Columns searched by all functions of a statement, none if its functions search different columns
+// Input: statement and columns of the enclosing scope, empty for all columns
+fn common_columns(statement: &Statement, scope: &[String]) -> Option<Vec<String>> {
+    match statement {
+        Statement::Column { columns, statement } => common_columns(statement, columns),
+        Statement::Infix {
+            statement,
+            second_statement,
+            ..
+        } => {
+            let columns = common_columns(statement, scope)?;
+            (common_columns(second_statement, scope)? == columns).then_some(columns)
+        }
+        Statement::Prefix { statement, .. } => common_columns(statement, scope),
+        _ => Some(scope.to_vec()),
+    }
+}
+
+// Split a statement into parts which each search one set of columns
+// A full-text table function searches one set of columns, so every part gets its own table function
+// The parts are combined by whether their table function found a row, NOT is only allowed behind AND as in MSSQL
+// Input: statement, columns of the enclosing scope and the parts found so far
+// Output: condition on the rows found by the parts
+fn split_scopes(
+    statement: &Statement,
+    scope: &[String],
+    parts: &mut Vec<(Vec<String>, Statement)>,
+) -> Result<String, GenerateError> {
+    if let Some(columns) = common_columns(statement, scope) {
+        parts.push((columns, statement.clone()));
+        return Ok(format!("KEY_TBL{}.[KEY] IS NOT NULL", parts.len() - 1));
+    }
+    match statement {
+        Statement::Column { columns, statement } => split_scopes(statement, columns, parts),
+        Statement::Infix {
+            statement: first,
+            operator,
+            second_statement: second,
+        } => {
+            let (first_negated, first) = split_scoped_not(*first.clone());
+            let (second_negated, second) = split_scoped_not(*second.clone());
+            let (first, second, sql_operator) =
+                match (*operator == Operator::And, first_negated, second_negated) {
+                    (true, false, false) => (first, second, "AND"),
+                    (false, false, false) => (first, second, "OR"),
+                    (true, false, true) => (first, second, "AND NOT"),
+                    (true, true, false) => (second, first, "AND NOT"),
+                    _ => return Err(GenerateError::MisplacedNot(statement.clone())),
+                };
+            Ok(format!(
+                "({} {} {})",
+                split_scopes(&first, scope, parts)?,
+                sql_operator,
+                split_scopes(&second, scope, parts)?
+            ))
+        }
+        statement => Err(GenerateError::MisplacedNot(statement.clone())),
+    }
+}
+
+// Statement without its column scope
+fn unscoped(statement: &Statement) -> &Statement {
+    match statement {
+        Statement::Column { statement, .. } => unscoped(statement),
+        statement => statement,
     }
-    sql_parts.push("') AS KEY_TBL ON FT_TBL.[ID] = KEY_TBL.[KEY] WHERE KEY_TBL.RANK > 5) AS FS_RESULT ORDER BY FS_RESULT.RANK DESC;".to_owned());
-    Ok(sql_parts.join(" "))
 }
 
 // Generator struct with current and next statements as attributes
@@ -52,7 +469,9 @@ impl<'p> Generator<'p> {
         if self.current == Statement::EoF {
             return Ok(None);
         }
-        Ok(Some(self.generate_statement(self.current.clone())?))
+        let sql = self.generate_statement(self.current.clone())?;
+        self.write();
+        Ok(Some(sql))
     }
 
     // Set current and peek one step further in the ast
@@ -70,35 +489,60 @@ impl<'p> Generator<'p> {
     // Output: string
     fn generate_statement(&mut self, statement: Statement) -> Result<String, GenerateError> {
         let sql: String = match statement {
+            // NOT is only allowed behind AND, so a negated first statement swaps places with the second
             Statement::Infix {
                 statement,
                 operator,
                 second_statement,
             } => {
-                let sql_parts = [
-                    self.generate_statement(*statement)?,
-                    self.generate_operator(operator)?,
-                    self.generate_statement(*second_statement)?,
-                ];
-                sql_parts.join(" ")
+                let (first_negated, first) = split_not(*statement.clone());
+                let (second_negated, second) = split_not(*second_statement.clone());
+                let (first, second, sql_operator) =
+                    match (operator == Operator::And, first_negated, second_negated) {
+                        (_, false, false) => (first, second, self.generate_operator(operator)?),
+                        (true, false, true) => (first, second, String::from("AND NOT")),
+                        (true, true, false) => (second, first, String::from("AND NOT")),
+                        _ => {
+                            return Err(GenerateError::MisplacedNot(Statement::Infix {
+                                statement,
+                                operator,
+                                second_statement,
+                            }))
+                        }
+                    };
+                format!(
+                    "{} {} {}",
+                    self.generate_operand(first)?,
+                    sql_operator,
+                    self.generate_operand(second)?
+                )
             }
+            // A negated statement is only valid if the negations cancel each other out
+            Statement::Prefix { .. } => match split_not(statement.clone()) {
+                (false, unnegated) => self.generate_statement(unnegated)?,
+                (true, _) => return Err(GenerateError::MisplacedNot(statement)),
+            },
             // Contains generates it's search condition without mutation
-            Statement::Contains { expression } => {
-                format!("{}", self.generate_expression(expression)?)
-            }
-            // Startswith adds a * to end of a word or before the last " in a phrase
+            Statement::Contains { expression } => self.generate_expression(expression)?,
+            // Startswith adds a * before the last " in a phrase, unless it is already written with a wildcard
+            // Words are quoted aswell, MSSQL only treats quoted terms as prefix terms
             Statement::Starts { expression } => {
                 let mut word_or_phrase = self.generate_expression(expression)?;
-                if word_or_phrase.starts_with('"') && word_or_phrase.ends_with('"') {
+                if word_or_phrase.ends_with("*\"") {
+                    word_or_phrase
+                } else if word_or_phrase.starts_with('"') && word_or_phrase.ends_with('"') {
                     word_or_phrase.insert(word_or_phrase.len() - 1, '*');
+                    word_or_phrase
                 } else {
-                    word_or_phrase.push('*');
+                    format!("\"{}*\"", word_or_phrase)
                 }
-                format!("{}", word_or_phrase)
             }
             // Inflection calls the inflection function from MSSQL
             Statement::Inflection { expression } => {
-                let mut word_or_phrase = self.generate_expression(expression)?;
+                let mut word_or_phrase = self.generate_expression(expression.clone())?;
+                if word_or_phrase.contains('*') {
+                    return Err(GenerateError::UnsupportedWildcard(expression.to_string()));
+                }
                 if word_or_phrase.starts_with('"') && word_or_phrase.ends_with('"') {
                     word_or_phrase.remove(0);
                     word_or_phrase.remove(word_or_phrase.len() - 1);
@@ -107,7 +551,10 @@ impl<'p> Generator<'p> {
             }
             // Thesaurus calls the thesaurus function from MSSQL
             Statement::Thesaurus { expression } => {
-                let mut word_or_phrase = self.generate_expression(expression)?;
+                let mut word_or_phrase = self.generate_expression(expression.clone())?;
+                if word_or_phrase.contains('*') {
+                    return Err(GenerateError::UnsupportedWildcard(expression.to_string()));
+                }
                 if word_or_phrase.starts_with('"') && word_or_phrase.ends_with('"') {
                     word_or_phrase.remove(0);
                     word_or_phrase.remove(word_or_phrase.len() - 1);
@@ -115,38 +562,57 @@ impl<'p> Generator<'p> {
                 format!("FORMSOF(THESAURUS,\"{}\")", word_or_phrase)
             }
             // Near generates a parameter list of all search criteria and proximity in the end
+            // Ordered near adds TRUE as third argument
             Statement::Near {
                 parameter,
                 proximity,
+                ordered,
             } => {
                 let mut sql_parts: Vec<String> = Vec::new();
-                sql_parts.push(format!("NEAR(("));
+                sql_parts.push(String::from("NEAR(("));
                 for expression in parameter {
-                    let string = self.generate_expression(expression)?;
-                    sql_parts.push(format!("{}", string));
+                    sql_parts.push(self.generate_expression(expression)?);
                     sql_parts.push(String::from(", "));
                 }
                 sql_parts.remove(sql_parts.len() - 1);
-                sql_parts.push(format!("), {})", self.generate_expression(proximity)?));
+                sql_parts.push(format!("), {}", self.generate_expression(proximity)?));
+                sql_parts.push(String::from(if ordered { ", TRUE)" } else { ")" }));
                 sql_parts.join("")
             }
             // Weighted generates tuples of search criteria and their respective weight
             Statement::Weighted { parameter } => {
                 let mut sql_parts: Vec<String> = Vec::new();
-                sql_parts.push(format!("ISABOUT("));
-                for (word_or_phrase_expr, weight_expr) in parameter {
-                    let word_or_phrase = self.generate_expression(word_or_phrase_expr)?;
+                sql_parts.push(String::from("ISABOUT("));
+                for (term_statement, weight_expr) in parameter {
+                    let term = self.generate_statement(term_statement)?;
                     let weight = self.generate_expression(weight_expr)?;
-                    sql_parts.push(format!("{} WEIGHT({})", word_or_phrase, weight));
+                    sql_parts.push(format!("{} WEIGHT({})", term, weight));
                     sql_parts.push(String::from(", "));
                 }
                 sql_parts.remove(sql_parts.len() - 1);
                 sql_parts.push(String::from(")"));
                 sql_parts.join("")
             }
-            _ => return Err(GenerateError::UnexpectedStatement(self.current.clone())),
+            // Fuzzy terms are expanded with a vocabulary before generation, MSSQL cannot match them itself
+            Statement::Fuzzy { term, .. } => {
+                return Err(GenerateError::MissingVocabulary(term.to_string()))
+            }
+            // Columns are already part of the table function
+            Statement::Column { statement, .. } => self.generate_statement(*statement)?,
+            // Freetext searches with its own table function and cannot be combined
+            Statement::Freetext { .. } => return Err(GenerateError::CombinedFreetext),
+            statement => return Err(GenerateError::UnexpectedStatement(statement)),
+        };
+        Ok(sql)
+    }
+
+    // Generate operand of an infix statement, nested infix statements are enclosed with parentheses
+    fn generate_operand(&mut self, statement: Statement) -> Result<String, GenerateError> {
+        let sql = match statement {
+            Statement::Infix { .. } => format!("( {} )", self.generate_statement(statement)?),
+            Statement::Column { statement, .. } => self.generate_operand(*statement)?,
+            statement => self.generate_statement(statement)?,
         };
-        self.write();
         Ok(sql)
     }
 
@@ -155,6 +621,11 @@ impl<'p> Generator<'p> {
     // Output: string
     fn generate_expression(&mut self, expression: Expression) -> Result<String, GenerateError> {
         let sql: String = match expression {
+            Expression::WordOrPhrase(s) if is_wildcard(&s) => generate_wildcard(s)?,
+            // Words that are operators in MSSQL must be searched for as phrase
+            Expression::WordOrPhrase(s) if SQL_KEYWORDS.contains(&s.to_uppercase().as_str()) => {
+                format!("\"{}\"", s)
+            }
             Expression::WordOrPhrase(s) => s,
             Expression::Number(u) => u.to_string(),
             Expression::ZeroToOne(f) => f.to_string(),
@@ -170,9 +641,8 @@ impl<'p> Generator<'p> {
                     String::from(")"),
                 ];
                 // If the second expression is a not operator it must write NOT before the parentheses
-                match *expr2 {
-                    Expression::Prefix(Operator::Not, ..) => sql_parts[4] = String::from("NOT ("),
-                    _ => (),
+                if let Expression::Prefix(Operator::Not, ..) = *expr2 {
+                    sql_parts[4] = String::from("NOT (");
                 }
                 sql_parts.join(" ")
             }
@@ -201,9 +671,287 @@ impl<'p> Generator<'p> {
     }
 }
 
+// Whether a term has a wildcard, * is a wildcard in words and phrases
+// ? is only a wildcard inside a word, at the end of a word or in a phrase it is punctuation like "what is love?"
+fn is_wildcard(term: &str) -> bool {
+    if term.starts_with('"') {
+        term.contains('*')
+    } else {
+        term.contains('*') || term.trim_end_matches('?').contains('?')
+    }
+}
+
+// Generate a wildcard term, MSSQL only supports a single * at the end of a word or phrase
+// Terms with a wildcard are always quoted, MSSQL treats unquoted ones as plain words
+// Input: word or phrase containing a wildcard
+// Output: string or error if the wildcard cannot be expressed
+fn generate_wildcard(term: String) -> Result<String, GenerateError> {
+    let phrase = term.starts_with('"');
+    let pattern = term.trim_matches('"');
+    match pattern.strip_suffix('*') {
+        Some(prefix)
+            if !prefix.trim().is_empty()
+                && !prefix.ends_with(' ')
+                && !prefix.contains('*')
+                && (phrase || !prefix.contains('?')) =>
+        {
+            Ok(format!("\"{}*\"", prefix))
+        }
+        _ => Err(GenerateError::UnsupportedWildcard(term)),
+    }
+}
+
+// Remove all NOT operators in front of a statement
+// Input: statement
+// Output: whether the statement is negated and the statement without NOT operators
+fn split_not(statement: Statement) -> (bool, Statement) {
+    match statement {
+        Statement::Prefix {
+            operator: Operator::Not,
+            statement,
+        } => {
+            let (negated, statement) = split_not(*statement);
+            (!negated, statement)
+        }
+        Statement::Column { statement, .. } => split_not(*statement),
+        statement => (false, statement),
+    }
+}
+
+// Split the negation from a statement like split_not, but keep the columns it searches
+// Input: statement
+// Output: whether the statement is negated and the statement without negation
+fn split_scoped_not(statement: Statement) -> (bool, Statement) {
+    match statement {
+        Statement::Prefix {
+            operator: Operator::Not,
+            statement,
+        } => {
+            let (negated, statement) = split_scoped_not(*statement);
+            (!negated, statement)
+        }
+        Statement::Column { columns, statement } => {
+            let (negated, statement) = split_scoped_not(*statement);
+            (
+                negated,
+                Statement::Column {
+                    columns,
+                    statement: Box::new(statement),
+                },
+            )
+        }
+        statement => (false, statement),
+    }
+}
+
 // Types of error covered by the generator
 #[derive(Debug, Error)]
 pub enum GenerateError {
     #[error("Unexpected statement {0:?}.")]
     UnexpectedStatement(Statement),
+    #[error("NOT is only allowed after AND, found {0:?}.")]
+    MisplacedNot(Statement),
+    #[error("Freetext cannot be combined with other functions.")]
+    CombinedFreetext,
+    #[error("Filters can only be combined with the search by AND, found {0:?}.")]
+    MisplacedFilter(Statement),
+    #[error("Filters need a search condition to restrict.")]
+    MissingSearchCondition,
+    #[error("MSSQL only supports a single * at the end of a word or phrase, found {0}.")]
+    UnsupportedWildcard(String),
+    #[error("Results cannot be sorted by {0}.")]
+    UnknownSortColumn(String),
+    #[error("A query can only select one language.")]
+    MultipleLanguages,
+    #[error("Fuzzy term {0} needs a vocabulary, MSSQL has no fuzzy matching.")]
+    MissingVocabulary(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_gen::vocabulary::Vocabulary;
+    use crate::{compile, CompileError};
+    use std::sync::Arc;
+
+    // Search condition inside the quotes of the full-text table function
+    fn condition(sql: &str) -> &str {
+        let start = sql.find(", '").unwrap() + 3;
+        let end = sql[start..].find("')").unwrap() + start;
+        sql[start..end].trim()
+    }
+
+    #[test]
+    fn rejects_fuzzy_terms_without_vocabulary() {
+        for query in ["rust~1", "@fuzzy:rust,1: & go"] {
+            assert!(matches!(
+                compile(query, &Options::default()),
+                Err(CompileError::Generate(GenerateError::MissingVocabulary(term))) if term == "rust"
+            ));
+        }
+    }
+
+    #[test]
+    fn expands_fuzzy_terms_with_vocabulary() {
+        let words = ["rust", "rusty", "trust", "go"].map(String::from).to_vec();
+        let options = Options {
+            vocabulary: Some(Arc::new(Vocabulary::new(words))),
+            ..Options::default()
+        };
+        let sql = compile("rust~1 & go", &options).unwrap();
+        assert_eq!(condition(&sql), "( ( rust OR rusty ) OR trust ) AND go");
+    }
+
+    fn compile_default(query: &str) -> String {
+        compile(query, &Options::default()).unwrap_or_else(|error| panic!("{}: {}", query, error))
+    }
+
+    #[test]
+    fn generates_negated_and_grouped_statements() {
+        assert_eq!(
+            condition(&compile_default("!@contains:rust: & @contains:go:")),
+            "go AND NOT rust"
+        );
+        assert_eq!(
+            condition(&compile_default(
+                "(@contains:a: | @contains:b:) & @contains:c:"
+            )),
+            "( a OR b ) AND c"
+        );
+        assert!(matches!(
+            compile("!@contains:rust:", &Options::default()),
+            Err(CompileError::Generate(GenerateError::MisplacedNot(_)))
+        ));
+    }
+
+    #[test]
+    fn generates_filters_as_parameters() {
+        let sql = compile_default("rust category=science modified:[2000-01-01 TO 2001-01-01]");
+        assert!(sql.contains("DECLARE @filter0 NVARCHAR(4000) = N'science';"));
+        assert!(sql.contains(
+            "DECLARE @filter1 DATE = '2000-01-01'; DECLARE @filter2 DATE = '2001-01-01';"
+        ));
+        assert!(sql.contains(
+            "AND FT_TBL.[Category] = @filter0 AND FT_TBL.[Modified] BETWEEN @filter1 AND @filter2)"
+        ));
+        assert!(matches!(
+            compile("size>5", &Options::default()),
+            Err(CompileError::Generate(
+                GenerateError::MissingSearchCondition
+            ))
+        ));
+        assert!(matches!(
+            compile("rust | size>5", &Options::default()),
+            Err(CompileError::Generate(GenerateError::MisplacedFilter(_)))
+        ));
+    }
+
+    #[test]
+    fn generates_bare_terms_and_keyword_operators() {
+        assert_eq!(condition(&compile_default("rust go")), "rust AND go");
+        assert_eq!(
+            condition(&compile_default("rust AND NOT go OR java")),
+            "( rust AND NOT go ) OR java"
+        );
+    }
+
+    #[test]
+    fn generates_near_and_weighted_terms() {
+        assert_eq!(
+            condition(&compile_default("@near:a,@sw:ru:,max,ordered:")),
+            "NEAR((a, \"ru*\"), MAX, TRUE)"
+        );
+        assert_eq!(
+            condition(&compile_default("@weighted:a,0.1,b,0.2,c,0.7:")),
+            "ISABOUT(a WEIGHT(0.1), b WEIGHT(0.2), c WEIGHT(0.7))"
+        );
+    }
+
+    #[test]
+    fn generates_freetext_with_language() {
+        let sql = compile_default("@freetext:rust language,de:");
+        assert!(sql.contains("FREETEXTTABLE([dbo].[Article], *, ' rust language ', LANGUAGE 1031)"));
+        assert!(matches!(
+            compile("@freetext:rust: & go", &Options::default()),
+            Err(CompileError::Generate(GenerateError::CombinedFreetext))
+        ));
+    }
+
+    #[test]
+    fn generates_columns_and_language() {
+        let sql = compile_default("@lang:de: title:rust");
+        assert!(sql.contains("CONTAINSTABLE([dbo].[Article], (Title), ' rust ', LANGUAGE 1031)"));
+        let options = Options {
+            language: Some(String::from("1033")),
+            ..Options::default()
+        };
+        assert!(compile("rust", &options)
+            .unwrap()
+            .contains("' rust ', LANGUAGE 1033)"));
+    }
+
+    #[test]
+    fn generates_a_table_function_per_column_set() {
+        for query in ["title:rust go", "@in:title:rust: & go"] {
+            let sql = compile_default(query);
+            assert!(sql.contains(
+                "LEFT JOIN CONTAINSTABLE([dbo].[Article], (Title), ' rust ') AS KEY_TBL0"
+            ));
+            assert!(sql.contains("LEFT JOIN CONTAINSTABLE([dbo].[Article], *, ' go ') AS KEY_TBL1"));
+            assert!(sql.contains(
+                "WHERE (KEY_TBL0.[KEY] IS NOT NULL AND KEY_TBL1.[KEY] IS NOT NULL) AND (ISNULL(KEY_TBL0.RANK, 0) + ISNULL(KEY_TBL1.RANK, 0)) > 5"
+            ));
+        }
+        let sql = compile_default("!title:rust & go");
+        assert!(sql.contains("(KEY_TBL0.[KEY] IS NOT NULL AND NOT KEY_TBL1.[KEY] IS NOT NULL)"));
+        assert!(sql.contains("(Title), ' rust ') AS KEY_TBL1"));
+        assert!(matches!(
+            compile("title:rust | !go", &Options::default()),
+            Err(CompileError::Generate(GenerateError::MisplacedNot(_)))
+        ));
+    }
+
+    #[test]
+    fn generates_sort_keys() {
+        let sql = compile_default("@sort:size,desc: rust");
+        assert!(sql.contains("FT_TBL.[Size] AS SORT_KEY0"));
+        assert!(sql.ends_with("ORDER BY FS_RESULT.SORT_KEY0 DESC, FS_RESULT.RANK DESC;"));
+    }
+
+    #[test]
+    fn generates_trailing_wildcards_only() {
+        assert_eq!(condition(&compile_default("ru*")), "\"ru*\"");
+        assert_eq!(
+            condition(&compile_default("\"rust lang*\"")),
+            "\"rust lang*\""
+        );
+        for query in ["r*st", "r?st", "wh?t?", "\"*rust\""] {
+            assert!(matches!(
+                compile(query, &Options::default()),
+                Err(CompileError::Generate(GenerateError::UnsupportedWildcard(
+                    _
+                )))
+            ));
+        }
+    }
+
+    #[test]
+    fn passes_question_marks_through_as_punctuation() {
+        assert_eq!(condition(&compile_default("what?")), "what?");
+        for query in ["\"what is love?\"", "@contains:\"what is love?\":"] {
+            assert_eq!(condition(&compile_default(query)), "\"what is love?\"");
+        }
+        assert_eq!(
+            condition(&compile_default("\"what is lo?e*\"")),
+            "\"what is lo?e*\""
+        );
+    }
+
+    #[test]
+    fn generates_expanded_macros() {
+        assert_eq!(
+            condition(&compile_default("$x = rust | go; $x & lang")),
+            "( rust OR go ) AND lang"
+        );
+    }
 }
@@ -1,10 +1,367 @@
-use logos::{Lexer, Logos};
+use logos::{Lexer, Logos, Span};
+use serde::Serialize;
+
+use crate::code_gen::ast::Comparison;
+use crate::code_gen::options::Options;
 
 // Main function to start lexing process
 // Input: string
 // Output: vec of tokens
 pub fn lex(input: &str) -> Vec<Token> {
-    Token::lexer(input).collect()
+    lex_with_options(input, &Options::default())
+}
+
+// Lexing process for a configured query language
+// Input: string and options
+// Output: vec of tokens
+pub fn lex_with_options(input: &str, options: &Options) -> Vec<Token> {
+    lex_spanned(input, options)
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect()
+}
+
+// Lexing process which keeps the position of every token in the input
+// Input: string and options
+// Output: vec of tokens and their byte ranges
+pub fn lex_spanned(input: &str, options: &Options) -> Vec<(Token, Span)> {
+    let tokens = Token::lexer(input)
+        .spanned()
+        .flat_map(|(token, span)| match token {
+            Token::WordOrPhrase(word) => match comparison(&word, &span, options) {
+                _ if is_macro(&word) => vec![(Token::Macro(word[1..].to_owned()), span)],
+                Some(tokens) => tokens,
+                None if options.keyword_operators => {
+                    vec![(keyword(&word).unwrap_or(Token::WordOrPhrase(word)), span)]
+                }
+                None => vec![(Token::WordOrPhrase(word), span)],
+            },
+            Token::UnknownFunction(name) => {
+                vec![(
+                    function(&name).unwrap_or(Token::UnknownFunction(name)),
+                    span,
+                )]
+            }
+            token => vec![(token, span)],
+        })
+        .collect();
+    dates(freetext(spaced_comparisons(tokens, options), input), input)
+}
+
+// The text of a freetext function is searched as written, so its operators and numbers are words
+// Tokens without whitespace between them are joined to one word, e.g. -good or rock&roll
+// Input: tokens, their byte ranges and the input they were lexed from
+// Output: tokens with the text of freetext functions as words
+fn freetext(tokens: Vec<(Token, Span)>, input: &str) -> Vec<(Token, Span)> {
+    let mut literal: Vec<(Token, Span)> = Vec::with_capacity(tokens.len());
+    let mut inside = false;
+    for (token, span) in tokens {
+        if !inside || !is_text(&token) {
+            inside = token == Token::Colon && matches!(literal.last(), Some((Token::Freetext, _)));
+            literal.push((token, span));
+            continue;
+        }
+        match literal.last_mut() {
+            Some((Token::WordOrPhrase(word), previous)) if previous.end == span.start => {
+                previous.end = span.end;
+                *word = input[previous.clone()].to_owned();
+            }
+            _ => literal.push((Token::WordOrPhrase(input[span.clone()].to_owned()), span)),
+        }
+    }
+    literal
+}
+
+// Tokens which are part of the text of a freetext function
+fn is_text(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::WordOrPhrase(_)
+            | Token::Date(_)
+            | Token::ZeroToOne(_)
+            | Token::Number(_)
+            | Token::Comparison(_)
+            | Token::Bang
+            | Token::Minus
+            | Token::And
+            | Token::Plus
+            | Token::Or
+            | Token::LeftParen
+            | Token::RightParen
+            | Token::LeftBracket
+            | Token::RightBracket
+            | Token::Tilde
+            | Token::Assign
+    )
+}
+
+// Numbers joined by - without whitespace are dates if they have the form year-month-day
+// A date regex would make logos misread numbers followed by a -, e.g. 1999-2000
+// Input: tokens, their byte ranges and the input they were lexed from
+// Output: tokens with dates instead of their numbers and minus signs
+fn dates(tokens: Vec<(Token, Span)>, input: &str) -> Vec<(Token, Span)> {
+    let mut joined: Vec<(Token, Span)> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Some(window) = tokens.get(i..i + 5) {
+            let span = window[0].1.start..window[4].1.end;
+            let adjacent = window
+                .windows(2)
+                .all(|pair| pair[0].1.end == pair[1].1.start);
+            if adjacent && is_date(&input[span.clone()]) {
+                joined.push((Token::Date(input[span.clone()].to_owned()), span));
+                i += 5;
+                continue;
+            }
+        }
+        joined.push(tokens[i].clone());
+        i += 1;
+    }
+    joined
+}
+
+// Dates have a four digit year, a one or two digit month and a one or two digit day
+fn is_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+    parts.len() == 3
+        && parts[0].len() == 4
+        && (1..=2).contains(&parts[1].len())
+        && (1..=2).contains(&parts[2].len())
+        && parts
+            .iter()
+            .all(|part| part.chars().all(|c| c.is_ascii_digit()))
+}
+
+// Lexing process for syntax highlighting, every token is classified
+// Input: string and options
+// Output: vec of highlighted tokens with character positions
+pub fn highlight(input: &str, options: &Options) -> Vec<Highlight> {
+    lex_spanned(input, options)
+        .into_iter()
+        .map(|(token, span)| Highlight {
+            text: input[span.clone()].to_owned(),
+            class: token.class(),
+            start: input[..span.start].chars().count(),
+            end: input[..span.end].chars().count(),
+        })
+        .collect()
+}
+
+// Token of a highlighted query, start and end are character positions in the query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Highlight {
+    pub text: String,
+    pub class: TokenClass,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Semantic classes of tokens, serialized in lowercase
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenClass {
+    Function,
+    Term,
+    Phrase,
+    Operator,
+    Number,
+    Error,
+}
+
+// Macro names are words starting with $ followed by letters and underscores
+fn is_macro(word: &str) -> bool {
+    word.len() > 1
+        && word.starts_with('$')
+        && word[1..]
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == '_')
+}
+
+// Comparison operators inside words, longer operators first so >= is not read as >
+const COMPARISONS: [(&str, Comparison); 6] = [
+    (">=", Comparison::GreaterEqual),
+    ("<=", Comparison::LessEqual),
+    ("<>", Comparison::NotEqual),
+    ("=", Comparison::Equal),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+// Words starting with a field name and a comparison operator are split into field, comparison and value
+// Only configured filter fields are split, so other words keep their = < and > characters
+// The value might also follow as its own token, e.g. year>=2010 is lexed as year>= and 2010
+// Input: word, its byte range and options with the filter fields
+// Output: tokens and their byte ranges, none if the word is no comparison
+fn comparison(word: &str, span: &Span, options: &Options) -> Option<Vec<(Token, Span)>> {
+    let field_end = word.find(|c: char| !c.is_ascii_alphabetic() && c != '_')?;
+    if !is_filter_field(&word[..field_end], options) {
+        return None;
+    }
+    let mut tokens = vec![(
+        Token::WordOrPhrase(word[..field_end].to_owned()),
+        span.start..span.start + field_end,
+    )];
+    tokens.extend(operator(
+        &word[field_end..],
+        span.start + field_end..span.end,
+    )?);
+    Some(tokens)
+}
+
+// Words starting with a comparison operator are split into comparison and value
+// Input: word and its byte range
+// Output: tokens and their byte ranges, none if the word starts with no comparison operator
+fn operator(word: &str, span: Span) -> Option<Vec<(Token, Span)>> {
+    let (operator, comparison) = COMPARISONS
+        .iter()
+        .find(|(operator, _)| word.starts_with(operator))?;
+    let value_start = span.start + operator.len();
+    let mut tokens = vec![(
+        Token::Comparison(comparison.clone()),
+        span.start..value_start,
+    )];
+    if value_start < span.end {
+        tokens.push((
+            Token::WordOrPhrase(word[operator.len()..].to_owned()),
+            value_start..span.end,
+        ));
+    }
+    Some(tokens)
+}
+
+// Filter fields are compared case-insensitive with the configured ones
+fn is_filter_field(word: &str, options: &Options) -> bool {
+    options
+        .filters
+        .iter()
+        .any(|(field, _)| field.eq_ignore_ascii_case(word))
+}
+
+// Comparisons can be separated from their filter field by whitespace, e.g. year >= 2010 or category = Physics
+// A lone = is lexed as assignment and other operators as the start of a word, both become comparisons
+// Input: tokens and their byte ranges, options with the filter fields
+// Output: tokens with comparisons after filter fields
+fn spaced_comparisons(tokens: Vec<(Token, Span)>, options: &Options) -> Vec<(Token, Span)> {
+    let mut split: Vec<(Token, Span)> = Vec::with_capacity(tokens.len());
+    for (token, span) in tokens {
+        let after_field = matches!(
+            split.last(),
+            Some((Token::WordOrPhrase(field), _)) if is_filter_field(field, options)
+        );
+        match token {
+            Token::WordOrPhrase(word) if after_field => match operator(&word, span.clone()) {
+                Some(tokens) => split.extend(tokens),
+                None => split.push((Token::WordOrPhrase(word), span)),
+            },
+            Token::Assign if after_field => {
+                split.push((Token::Comparison(Comparison::Equal), span))
+            }
+            token => split.push((token, span)),
+        }
+    }
+    split
+}
+
+// Keyword operators are words which are replaced by their operator token, AND NOT becomes & !
+// Phrases keep their quotes, so a quoted keyword is never an operator
+fn keyword(word: &str) -> Option<Token> {
+    match word.to_lowercase().as_str() {
+        "and" => Some(Token::And),
+        "or" => Some(Token::Or),
+        "not" => Some(Token::Bang),
+        _ => None,
+    }
+}
+
+// Function names come before their short aliases
+const FULL_FUNCTION_NAMES: usize = 13;
+
+// Function names and their short aliases, both case-insensitive
+const FUNCTION_NAMES: [&str; 20] = [
+    "@contains",
+    "@startswith",
+    "@inflection",
+    "@thesaurus",
+    "@near",
+    "@weighted",
+    "@freetext",
+    "@in",
+    "@lang",
+    "@where",
+    "@range",
+    "@sort",
+    "@fuzzy",
+    "@c",
+    "@sw",
+    "@inf",
+    "@syn",
+    "@n",
+    "@w",
+    "@ft",
+];
+
+// Resolve a function name to its token
+fn function(name: &str) -> Option<Token> {
+    match name.to_lowercase().as_str() {
+        "@contains" | "@c" => Some(Token::Contains),
+        "@startswith" | "@sw" => Some(Token::Starts),
+        "@inflection" | "@inf" => Some(Token::Inflection),
+        "@thesaurus" | "@syn" => Some(Token::Thesaurus),
+        "@near" | "@n" => Some(Token::Near),
+        "@weighted" | "@w" => Some(Token::Weighted),
+        "@freetext" | "@ft" => Some(Token::Freetext),
+        "@in" => Some(Token::In),
+        "@lang" => Some(Token::Lang),
+        "@where" => Some(Token::Where),
+        "@range" => Some(Token::Range),
+        "@sort" => Some(Token::Sort),
+        "@fuzzy" => Some(Token::Fuzzy),
+        _ => None,
+    }
+}
+
+// Suggest the function that was most likely meant by an unknown function name
+// Input: unknown function name
+// Output: function whose name starts with the given one or is closest to it, none if nothing is similar enough
+pub fn suggest_function(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    if let Some(full_name) = FUNCTION_NAMES.iter().find(|f| f.starts_with(&name)) {
+        return Some(full_name);
+    }
+    let max_distance = 1.max(name.chars().count() / 3);
+    FUNCTION_NAMES
+        .iter()
+        .map(|f| (edit_distance(&name, f), *f))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, f)| f)
+}
+
+// Complete a function name, aliases are not suggested as they are only short forms
+// Input: beginning of a function name including the @
+// Output: full function names starting with it
+pub fn complete_function(prefix: &str) -> Vec<&'static str> {
+    let prefix = prefix.to_lowercase();
+    FUNCTION_NAMES[..FULL_FUNCTION_NAMES]
+        .iter()
+        .filter(|f| f.starts_with(&prefix))
+        .copied()
+        .collect()
+}
+
+// Levenshtein distance, number of inserted, removed or replaced characters to turn one string into another
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != *b_char);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
 
 // helper function to format strings
@@ -15,20 +372,23 @@ fn to_string(lex: &mut Lexer<Token>) -> Option<String> {
 
 // helper function to format floats
 fn to_float(lex: &mut Lexer<Token>) -> Option<f64> {
-    Some(lex.slice().parse().ok()?)
+    lex.slice().parse().ok()
 }
 
 // helper function to format unsigned integer
 fn to_u64(lex: &mut Lexer<Token>) -> Option<u64> {
-    Some(lex.slice().parse().ok()?)
+    lex.slice().parse().ok()
 }
 
 // List of all tokens that are accepted by the language
-#[derive(Debug, Clone, Logos, PartialEq)]
+#[derive(Debug, Clone, Logos, PartialEq, Serialize)]
 pub enum Token {
     // Regex: phrase starting and ending with " and escaped character \" or just a word allowing a list of special characters
-    #[regex(r##""(?:[^"\\]|\\.)*"|[a-zA-Zß?üÜöÖäÄ;\._<>´`#§$%/\\=€]+"##, to_string)]
+    // * and ? are wildcards in words, the generator checks whether the backend supports their position
+    #[regex(r##""(?:[^"\\]|\\.)*"|[a-zA-Zß?*üÜöÖäÄ\._<>´`#§$%/\\=€]+"##, to_string)]
     WordOrPhrase(String),
+    // ISO date with four digit year, joined from numbers after lexing
+    Date(String),
     // Regex: any float between 0 and 1
     #[regex(r"0+(\.[0-9]+)?|1", to_float)]
     ZeroToOne(f64),
@@ -53,22 +413,44 @@ pub enum Token {
     LeftParen,
     #[token(")")]
     RightParen,
+    // Brackets for the bounds of ranges
+    #[token("[")]
+    LeftBracket,
+    #[token("]")]
+    RightBracket,
     // Comma for parameter separation
     #[token(",")]
     Comma,
-    // Functions
-    #[token("@contains")]
+    // Tilde for fuzzy terms
+    #[token("~")]
+    Tilde,
+    // Macro definitions assign a statement to a name and end with a semicolon
+    #[token("=")]
+    Assign,
+    #[token(";")]
+    Semicolon,
+    // Macro names, resolved from words starting with $ after lexing
+    Macro(String),
+    // Comparisons of filters, != is a token as ! is no word character, the others are split from words
+    #[token("!=", |_| Comparison::NotEqual)]
+    Comparison(Comparison),
+    // Functions, resolved from their names after lexing
     Contains,
-    #[token("@startswith")]
     Starts,
-    #[token("@inflection")]
     Inflection,
-    #[token("@thesaurus")]
     Thesaurus,
-    #[token("@near")]
     Near,
-    #[token("@weighted")]
     Weighted,
+    Freetext,
+    In,
+    Lang,
+    Where,
+    Range,
+    Sort,
+    Fuzzy,
+    // Regex: @ followed by a name, only names which are no function remain after lexing
+    #[regex(r"@[a-zA-Z]+", to_string)]
+    UnknownFunction(String),
     // Colon to surround functions parameters
     #[token(":")]
     Colon,
@@ -80,12 +462,168 @@ pub enum Token {
     Error,
 }
 
-// Enable tokens to be casted as strings
-impl Into<String> for Token {
-    fn into(self) -> String {
+impl Token {
+    // Semantic class of a token for syntax highlighting
+    // Unknown functions are errors, punctuation like colons and commas counts as operator
+    pub fn class(&self) -> TokenClass {
         match self {
+            Token::WordOrPhrase(word) if word.starts_with('"') => TokenClass::Phrase,
+            Token::WordOrPhrase(_) | Token::Date(_) | Token::Macro(_) => TokenClass::Term,
+            Token::ZeroToOne(_) | Token::Number(_) => TokenClass::Number,
+            Token::Contains
+            | Token::Starts
+            | Token::Inflection
+            | Token::Thesaurus
+            | Token::Near
+            | Token::Weighted
+            | Token::Freetext
+            | Token::In
+            | Token::Lang
+            | Token::Where
+            | Token::Range
+            | Token::Sort
+            | Token::Fuzzy => TokenClass::Function,
+            Token::UnknownFunction(_) | Token::EoF | Token::Error => TokenClass::Error,
+            Token::Bang
+            | Token::Minus
+            | Token::And
+            | Token::Plus
+            | Token::Or
+            | Token::LeftParen
+            | Token::RightParen
+            | Token::LeftBracket
+            | Token::RightBracket
+            | Token::Comma
+            | Token::Tilde
+            | Token::Assign
+            | Token::Semicolon
+            | Token::Comparison(_)
+            | Token::Colon => TokenClass::Operator,
+        }
+    }
+}
+
+// Enable tokens to be casted as strings
+impl From<Token> for String {
+    fn from(token: Token) -> Self {
+        match token {
             Token::WordOrPhrase(s) => s,
             _ => unreachable!(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_comparisons_of_filter_fields_only() {
+        assert_eq!(
+            lex("year>=2010"),
+            vec![
+                Token::WordOrPhrase("year".to_owned()),
+                Token::Comparison(Comparison::GreaterEqual),
+                Token::Number(2010)
+            ]
+        );
+        assert_eq!(lex("x=y"), vec![Token::WordOrPhrase("x=y".to_owned())]);
+        assert_eq!(
+            lex("year >= 2010 category =x"),
+            vec![
+                Token::WordOrPhrase("year".to_owned()),
+                Token::Comparison(Comparison::GreaterEqual),
+                Token::Number(2010),
+                Token::WordOrPhrase("category".to_owned()),
+                Token::Comparison(Comparison::Equal),
+                Token::WordOrPhrase("x".to_owned())
+            ]
+        );
+        assert_eq!(
+            lex("x >= y"),
+            vec![
+                Token::WordOrPhrase("x".to_owned()),
+                Token::WordOrPhrase(">=".to_owned()),
+                Token::WordOrPhrase("y".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_tokens_with_character_positions() {
+        let highlighted: Vec<(String, TokenClass, usize, usize)> = highlight(
+            "@contains:\"grüße\": & tür 0.5 year>2010",
+            &Options::default(),
+        )
+        .into_iter()
+        .map(|token| (token.text, token.class, token.start, token.end))
+        .collect();
+        let expected = [
+            ("@contains", TokenClass::Function, 0, 9),
+            (":", TokenClass::Operator, 9, 10),
+            ("\"grüße\"", TokenClass::Phrase, 10, 17),
+            (":", TokenClass::Operator, 17, 18),
+            ("&", TokenClass::Operator, 19, 20),
+            ("tür", TokenClass::Term, 21, 24),
+            ("0.5", TokenClass::Number, 25, 28),
+            ("year", TokenClass::Term, 29, 33),
+            (">", TokenClass::Operator, 33, 34),
+            ("2010", TokenClass::Number, 34, 38),
+        ]
+        .map(|(text, class, start, end)| (text.to_owned(), class, start, end));
+        assert_eq!(highlighted, expected);
+    }
+
+    #[test]
+    fn highlights_unknown_functions_and_characters_as_errors() {
+        let highlighted = highlight("ä @bogus ^", &Options::default());
+        assert_eq!(
+            highlighted
+                .iter()
+                .map(|token| (token.class, token.start, token.end))
+                .collect::<Vec<_>>(),
+            vec![
+                (TokenClass::Term, 0, 1),
+                (TokenClass::Error, 2, 8),
+                (TokenClass::Error, 9, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_dates() {
+        assert_eq!(
+            lex("2020-01-05 2020-1-1"),
+            vec![
+                Token::Date("2020-01-05".to_owned()),
+                Token::Date("2020-1-1".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_numbers_followed_by_minus() {
+        assert_eq!(
+            lex("1999-2000"),
+            vec![Token::Number(1999), Token::Minus, Token::Number(2000)]
+        );
+        assert_eq!(
+            lex("1234-5"),
+            vec![Token::Number(1234), Token::Minus, Token::Number(5)]
+        );
+    }
+
+    #[test]
+    fn does_not_join_dates_across_whitespace() {
+        assert_eq!(
+            lex("2020 -1-1"),
+            vec![
+                Token::Number(2020),
+                Token::Minus,
+                Token::ZeroToOne(1.0),
+                Token::Minus,
+                Token::ZeroToOne(1.0)
+            ]
+        );
+    }
+}
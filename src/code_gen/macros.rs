@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::code_gen::ast::Statement;
+
+// Maximal number of statements a query can expand to
+// Every macro which uses another macro twice doubles the size, so a short query could otherwise use up all memory
+const MAX_STATEMENTS: usize = 10_000;
+
+// Expansion pass between parsing and generation, macros are replaced by the statements they are defined as
+// Macros can be used before their definition and definitions can use other macros
+// Input: abstract syntax tree
+// Output: abstract syntax tree without definitions and macros or error
+pub fn expand_macros(ast: Vec<Statement>) -> Result<Vec<Statement>, MacroError> {
+    let mut definitions: Vec<(String, Statement)> = Vec::new();
+    let mut statements: Vec<Statement> = Vec::new();
+    for statement in ast {
+        match statement {
+            Statement::Define { name, statement } => {
+                if definitions.iter().any(|(defined, _)| *defined == name) {
+                    return Err(MacroError::Redefined(name));
+                }
+                definitions.push((name, *statement));
+            }
+            statement => statements.push(statement),
+        }
+    }
+    let names: Vec<String> = definitions.iter().map(|(name, _)| name.clone()).collect();
+    let mut expander = Expander {
+        definitions,
+        resolved: HashMap::new(),
+    };
+    // Unused definitions are resolved aswell, so every cycle is reported
+    for name in &names {
+        expander.resolve(name, &mut Vec::new())?;
+    }
+    let statements = statements
+        .into_iter()
+        .map(|statement| expander.expand(statement, &mut Vec::new()))
+        .collect::<Result<Vec<Statement>, MacroError>>()?;
+    if statements.iter().map(count).sum::<usize>() > MAX_STATEMENTS {
+        return Err(MacroError::TooLarge(MAX_STATEMENTS));
+    }
+    Ok(statements)
+}
+
+// Expander keeps the definitions of a query and every definition once it is resolved
+struct Expander {
+    definitions: Vec<(String, Statement)>,
+    resolved: HashMap<String, Statement>,
+}
+
+impl Expander {
+    // Replace all macros in a statement and its nested statements
+    // Input: statement and names of the macros which are currently resolved
+    // Output: statement without macros or error
+    fn expand(
+        &mut self,
+        statement: Statement,
+        stack: &mut Vec<String>,
+    ) -> Result<Statement, MacroError> {
+        let statement = match statement {
+            Statement::Macro { name } => self.resolve(&name, stack)?,
+            Statement::Infix {
+                statement,
+                operator,
+                second_statement,
+            } => Statement::Infix {
+                statement: Box::new(self.expand(*statement, stack)?),
+                operator,
+                second_statement: Box::new(self.expand(*second_statement, stack)?),
+            },
+            Statement::Prefix {
+                operator,
+                statement,
+            } => Statement::Prefix {
+                operator,
+                statement: Box::new(self.expand(*statement, stack)?),
+            },
+            Statement::Column { columns, statement } => Statement::Column {
+                columns,
+                statement: Box::new(self.expand(*statement, stack)?),
+            },
+            statement => statement,
+        };
+        Ok(statement)
+    }
+
+    // Resolve a macro to its expanded definition, every definition is only expanded once
+    // A macro which is already being resolved uses itself, which would never end
+    fn resolve(&mut self, name: &str, stack: &mut Vec<String>) -> Result<Statement, MacroError> {
+        if let Some(statement) = self.resolved.get(name) {
+            return Ok(statement.clone());
+        }
+        if stack.iter().any(|resolving| resolving == name) {
+            let mut cycle: Vec<String> = stack.iter().map(|name| format!("${}", name)).collect();
+            cycle.push(format!("${}", name));
+            return Err(MacroError::Cycle(cycle.join(" -> ")));
+        }
+        let definition = self
+            .definitions
+            .iter()
+            .find(|(defined, _)| defined == name)
+            .map(|(_, statement)| statement.clone())
+            .ok_or_else(|| MacroError::Undefined(name.to_owned()))?;
+        stack.push(name.to_owned());
+        let statement = self.expand(definition, stack)?;
+        stack.pop();
+        if count(&statement) > MAX_STATEMENTS {
+            return Err(MacroError::TooLarge(MAX_STATEMENTS));
+        }
+        self.resolved.insert(name.to_owned(), statement.clone());
+        Ok(statement)
+    }
+}
+
+// Number of statements in a statement and its nested statements
+fn count(statement: &Statement) -> usize {
+    match statement {
+        Statement::Infix {
+            statement,
+            second_statement,
+            ..
+        } => 1 + count(statement) + count(second_statement),
+        Statement::Prefix { statement, .. } | Statement::Column { statement, .. } => {
+            1 + count(statement)
+        }
+        _ => 1,
+    }
+}
+
+// Types of errors covered by the macro expansion
+#[derive(Debug, Error)]
+pub enum MacroError {
+    #[error("Undefined macro ${0}.")]
+    Undefined(String),
+    #[error("Macro ${0} is defined more than once.")]
+    Redefined(String),
+    #[error("Macro uses itself: {0}.")]
+    Cycle(String),
+    #[error("Macros expand to more than {0} statements.")]
+    TooLarge(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_gen::lexer::lex;
+    use crate::code_gen::parser::parse;
+
+    fn expand_query(query: &str) -> Result<Vec<Statement>, MacroError> {
+        expand_macros(parse(lex(query)).unwrap())
+    }
+
+    #[test]
+    fn expands_macros_used_before_their_definition() {
+        assert_eq!(
+            expand_query("$x = $y | go; $y = rust; $x & lang").unwrap(),
+            parse(lex("(rust | go) & lang")).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_undefined_redefined_and_cyclic_macros() {
+        assert!(matches!(
+            expand_query("$x & lang"),
+            Err(MacroError::Undefined(name)) if name == "x"
+        ));
+        assert!(matches!(
+            expand_query("$x = a; $x = b; $x"),
+            Err(MacroError::Redefined(name)) if name == "x"
+        ));
+        assert!(matches!(
+            expand_query("$x = $y; $y = a | $x; a"),
+            Err(MacroError::Cycle(cycle)) if cycle == "$x -> $y -> $x"
+        ));
+    }
+
+    #[test]
+    fn rejects_doubling_chains() {
+        let mut query = String::from("$a = x | y;");
+        let names: Vec<char> = ('a'..='v').collect();
+        for pair in names.windows(2) {
+            query.push_str(&format!(" ${1} = ${0} | ${0};", pair[0], pair[1]));
+        }
+        query.push_str(" $v");
+        assert!(matches!(
+            expand_query(&query),
+            Err(MacroError::TooLarge(MAX_STATEMENTS))
+        ));
+        // References to a large definition add up aswell
+        assert!(matches!(
+            expand_query("$a = x | y; $b = $a | $a; $c = $b | $b; $d = $c | $c; $e = $d | $d; $f = $e | $e; $g = $f | $f; $h = $g | $g; $i = $h | $h; $j = $i | $i; $k = $j | $j; $k & $k & $k"),
+            Err(MacroError::TooLarge(_))
+        ));
+    }
+}
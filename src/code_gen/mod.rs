@@ -1,4 +1,9 @@
-mod ast;
+pub mod ast;
+pub mod completion;
 pub mod generator;
 pub mod lexer;
+pub mod macros;
+pub mod options;
 pub mod parser;
+pub mod printer;
+pub mod vocabulary;
@@ -0,0 +1,212 @@
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::code_gen::vocabulary::Vocabulary;
+
+// Default database constants
+const DB_NAME: &str = "Wikipedia";
+const TBL_NAME: &str = "[dbo].[Article]";
+const RETURN_ATTRIBUTE: &str = "Title";
+const TOP_ROWS: u64 = 5;
+const COLUMNS: [&str; 1] = ["Title"];
+// Column holding the rank of a hit in the result of a full-text table function
+pub const RANK: &str = "RANK";
+const FILTERS: [(&str, FieldType); 4] = [
+    ("Category", FieldType::Text),
+    ("Year", FieldType::Integer),
+    ("Size", FieldType::Integer),
+    ("Modified", FieldType::Date),
+];
+
+// Options to configure the query language and the code generation
+// Keyword operators enable AND, OR and NOT as words in any case, quoted they are still searched for
+// Normalized weights can be any positive numbers which are scaled to add up to 1
+// Columns are the full-text indexed columns a search can be restricted to
+// Filters are the metadata fields results can be restricted by and the type of their values
+// Sort is the column and order of results unless the query sorts them, rank is always the last tiebreaker
+// Vocabulary are the indexed words fuzzy terms are expanded to, it is loaded once and shared by all searches
+// Language is used for word breaking and stemming unless the query selects one, none uses the server default
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub db_name: String,
+    pub table: String,
+    pub return_attribute: String,
+    pub top_rows: u64,
+    pub backend: Backend,
+    pub keyword_operators: bool,
+    pub normalize_weights: bool,
+    pub columns: Vec<String>,
+    pub filters: Vec<(String, FieldType)>,
+    pub sort: Option<(String, Order)>,
+    pub language: Option<String>,
+    pub vocabulary: Option<Arc<Vocabulary>>,
+}
+
+// Backends the query can be compiled for, currently only MSSQL full-text search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Mssql,
+}
+
+// Backends are selected by their lowercase name
+impl FromStr for Backend {
+    type Err = OptionsError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "mssql" => Ok(Self::Mssql),
+            _ => Err(OptionsError::UnknownBackend(name.to_owned())),
+        }
+    }
+}
+
+// Order results are sorted in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Order {
+    Ascending,
+    Descending,
+}
+
+// Orders are selected by their lowercase name or abbreviation
+impl FromStr for Order {
+    type Err = OptionsError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "asc" | "ascending" => Ok(Self::Ascending),
+            "desc" | "descending" => Ok(Self::Descending),
+            _ => Err(OptionsError::UnknownOrder(name.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ascending => write!(f, "asc"),
+            Self::Descending => write!(f, "desc"),
+        }
+    }
+}
+
+// Rank is sorted descending by default so the best hit comes first, any other column ascending
+pub fn default_order(column: &str) -> Order {
+    if column == RANK {
+        Order::Descending
+    } else {
+        Order::Ascending
+    }
+}
+
+// Types of metadata fields, filter values are checked against them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    Text,
+    Integer,
+    Date,
+}
+
+// Field types are selected by their lowercase name
+impl FromStr for FieldType {
+    type Err = OptionsError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "integer" => Ok(Self::Integer),
+            "date" => Ok(Self::Date),
+            _ => Err(OptionsError::UnknownFieldType(name.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Integer => write!(f, "integer"),
+            Self::Date => write!(f, "date (YYYY-MM-DD)"),
+        }
+    }
+}
+
+// Default options search the Wikipedia articles
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            db_name: DB_NAME.to_owned(),
+            table: TBL_NAME.to_owned(),
+            return_attribute: RETURN_ATTRIBUTE.to_owned(),
+            top_rows: TOP_ROWS,
+            backend: Backend::Mssql,
+            keyword_operators: true,
+            normalize_weights: false,
+            columns: COLUMNS.iter().map(|column| column.to_string()).collect(),
+            filters: FILTERS
+                .iter()
+                .map(|(field, field_type)| (field.to_string(), *field_type))
+                .collect(),
+            sort: None,
+            language: None,
+            vocabulary: None,
+        }
+    }
+}
+
+impl Options {
+    // Results can be sorted by rank, the returned attribute, the searchable columns and the filter fields
+    // Only these names ever reach the generated SQL, any other sort column is rejected
+    pub fn sort_columns(&self) -> Vec<String> {
+        let mut sort_columns = vec![RANK.to_owned(), self.return_attribute.clone()];
+        let columns = self.columns.iter();
+        let fields = self.filters.iter().map(|(field, _)| field);
+        for column in columns.chain(fields) {
+            if !sort_columns
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(column))
+            {
+                sort_columns.push(column.clone());
+            }
+        }
+        sort_columns
+    }
+
+    // Look up a sort column, compared case-insensitive
+    // Input: column name
+    // Output: column name as configured, none if results cannot be sorted by it
+    pub fn sort_column(&self, name: &str) -> Option<String> {
+        self.sort_columns()
+            .into_iter()
+            .find(|column| column.eq_ignore_ascii_case(name))
+    }
+
+    // Set the sort option from its column and optional order separated by a comma, e.g. title,desc
+    pub fn set_sort(&mut self, sort: &str) -> Result<(), OptionsError> {
+        let (name, order) = match sort.split_once(',') {
+            Some((name, order)) => (name.trim(), Some(order.trim().parse::<Order>()?)),
+            None => (sort.trim(), None),
+        };
+        let column = self
+            .sort_column(name)
+            .ok_or_else(|| OptionsError::UnknownSortColumn(name.to_owned()))?;
+        let order = order.unwrap_or_else(|| default_order(&column));
+        self.sort = Some((column, order));
+        Ok(())
+    }
+}
+
+// Types of errors covered by the options
+#[derive(Debug, Error)]
+pub enum OptionsError {
+    #[error("Unknown backend {0}.")]
+    UnknownBackend(String),
+    #[error("Unknown field type {0}.")]
+    UnknownFieldType(String),
+    #[error("Unknown sort order {0}, expected asc or desc.")]
+    UnknownOrder(String),
+    #[error("Results cannot be sorted by {0}.")]
+    UnknownSortColumn(String),
+}
@@ -2,21 +2,54 @@ use std::slice::Iter;
 use thiserror::Error;
 
 use crate::code_gen::ast::*;
-use crate::code_gen::lexer::Token;
+use crate::code_gen::lexer::{suggest_function, Token};
+use crate::code_gen::options::{default_order, FieldType, Options, Order};
+
+// Number of terms MSSQL allows in a near function
+const NEAR_MIN_TERMS: usize = 2;
+const NEAR_MAX_TERMS: usize = 64;
+// Default and maximum edit distance of fuzzy terms
+const FUZZY_DISTANCE: u64 = 2;
+const FUZZY_MAX_DISTANCE: u64 = 2;
+// Allowed deviation of the sum of all weights from 1 due to floating-point rounding
+const WEIGHT_TOLERANCE: f64 = 1e-9;
 
 // Main function to start parsing process
 // Input: vec of tokens
 // Ouput: abstract syntax tree (vec of statements)
 pub fn parse(tokens: Vec<Token>) -> Result<Vec<Statement>, ParseError> {
-    let mut parser = Parser::new(tokens.iter());
+    parse_with_options(tokens, &Options::default())
+}
+
+// Parsing process for a configured query language
+// Input: vec of tokens and options
+// Output: abstract syntax tree (vec of statements)
+pub fn parse_with_options(
+    tokens: Vec<Token>,
+    options: &Options,
+) -> Result<Vec<Statement>, ParseError> {
+    parse_with_position(tokens, options).map_err(|(error, _)| error)
+}
+
+// Parsing process which also reports where an error occurred
+// Input: vec of tokens and options
+// Output: abstract syntax tree or error and the index of the token it occurred at
+pub fn parse_with_position(
+    tokens: Vec<Token>,
+    options: &Options,
+) -> Result<Vec<Statement>, (ParseError, usize)> {
+    let mut parser = Parser::new(tokens.iter(), options);
     // read twice to overwrite intial EoF tokens
     parser.read();
     parser.read();
     let mut ast: Vec<Statement> = Vec::new();
-    while let Some(statement) = parser.next()? {
-        ast.push(statement);
+    loop {
+        match parser.next() {
+            Ok(Some(statement)) => ast.push(statement),
+            Ok(None) => return Ok(ast),
+            Err(error) => return Err((error, parser.position())),
+        }
     }
-    Ok(ast)
 }
 
 // Precedence to enable priorities between operators
@@ -37,48 +70,101 @@ impl Precedence {
     fn token(token: Token) -> Self {
         match token {
             Token::Bang | Token::Minus => Self::Not,
-            Token::Plus | Token::And | Token::WordOrPhrase(..) => Self::And,
-            Token::Or => Self::Or,
-            Token::LeftParen => Self::Group,
-            Token::Contains
+            // Terms and functions following each other are combined with an implicit AND
+            Token::Plus
+            | Token::And
+            | Token::WordOrPhrase(..)
+            | Token::Date(..)
+            | Token::Number(..)
+            | Token::ZeroToOne(..)
+            | Token::Contains
             | Token::Starts
             | Token::Inflection
             | Token::Thesaurus
             | Token::Near
-            | Token::Weighted => Self::Statement,
+            | Token::Weighted
+            | Token::Freetext
+            | Token::In
+            | Token::Where
+            | Token::Range
+            | Token::Fuzzy
+            | Token::Macro(..) => Self::And,
+            Token::Or => Self::Or,
+            Token::LeftParen => Self::Group,
             _ => Self::Lowest,
         }
     }
+
+    // Groups following a statement are combined with an implicit AND like any other statement
+    fn statement_token(token: Token) -> Self {
+        match token {
+            Token::LeftParen => Self::And,
+            token => Self::token(token),
+        }
+    }
 }
 
 // Parser saves current and next tokens as attribute
+// Read counts how often the parser moved forward to know the position of the current token
 struct Parser<'p> {
     tokens: Iter<'p, Token>,
     current: Token,
     peek: Token,
+    read: usize,
+    options: &'p Options,
+    in_columns: bool,
 }
 
 impl<'p> Parser<'p> {
     // Initial parser creation
-    fn new(tokens: Iter<'p, Token>) -> Self {
+    fn new(tokens: Iter<'p, Token>, options: &'p Options) -> Self {
         Self {
             tokens,
             current: Token::EoF,
             peek: Token::EoF,
+            read: 0,
+            options,
+            in_columns: false,
         }
     }
 
+    // Index of the current token, the first two reads only fill current and peek
+    fn position(&self) -> usize {
+        self.read.saturating_sub(2)
+    }
+
     // Parse next statement if possible
+    // Language and sort options are only allowed between statements as they apply to the whole query
+    // Macro definitions are only allowed there as well, they end with a semicolon
     // Output: statement or error
     fn next(&mut self) -> Result<Option<Statement>, ParseError> {
-        if self.current == Token::EoF {
-            return Ok(None);
+        match self.current.clone() {
+            Token::EoF => Ok(None),
+            Token::Lang => {
+                self.expect_token_and_read(Token::Lang)?;
+                self.expect_token_and_read(Token::Colon)?;
+                let language = self.parse_language()?;
+                self.expect_token_and_read(Token::Colon)?;
+                Ok(Some(Statement::Language { language }))
+            }
+            Token::Sort => Ok(Some(self.parse_sort()?)),
+            Token::Macro(name) if self.peek == Token::Assign => {
+                self.read();
+                self.expect_token_and_read(Token::Assign)?;
+                let statement = self.parse_statement(Precedence::Lowest)?;
+                self.expect_token_and_read(Token::Semicolon)?;
+                Ok(Some(Statement::Define {
+                    name,
+                    statement: Box::new(statement),
+                }))
+            }
+            _ => Ok(Some(self.parse_statement(Precedence::Lowest)?)),
         }
-        Ok(Some(self.parse_statement(Precedence::Lowest)?))
     }
 
     // Set current and peek one step further in the vec of tokens
     fn read(&mut self) {
+        self.read += 1;
         self.current = self.peek.clone();
         self.peek = if let Some(token) = self.tokens.next() {
             token.clone()
@@ -113,14 +199,83 @@ impl<'p> Parser<'p> {
         Ok(result)
     }
 
-    // Parse statement, can only be a function or combination of functions
+    // Parse statement, can be a function, a bare term, a negated or grouped statement or combination of those
     // Input: precedence
     // Output: statement or error
     fn parse_statement(&mut self, precedence: Precedence) -> Result<Statement, ParseError> {
         let mut statement = match self.current.clone() {
+            // A term followed by a comparison filters the results by a metadata field
+            Token::WordOrPhrase(..) if matches!(self.peek, Token::Comparison(..)) => {
+                self.parse_filter()?
+            }
+            // A word followed by a tilde is a fuzzy term, the distance behind the tilde is optional
+            Token::WordOrPhrase(..) if self.peek == Token::Tilde => {
+                let term = self.parse_fuzzy_term()?;
+                self.expect_token_and_read(Token::Tilde)?;
+                let distance = match self.integer() {
+                    Some(_) => self.parse_fuzzy_distance()?,
+                    None => FUZZY_DISTANCE,
+                };
+                Statement::Fuzzy { term, distance }
+            }
+            // A term followed by a colon and a bracket is a range on a metadata field
+            Token::WordOrPhrase(..)
+                if self.peek == Token::Colon
+                    && self.tokens.clone().next() == Some(&Token::LeftBracket) =>
+            {
+                self.parse_bounds()?
+            }
+            // A term followed by a colon is a column, the next statement is only searched in that column
+            // Inside an in function the colon closes the function instead
+            Token::WordOrPhrase(name) if self.peek == Token::Colon && !self.in_columns => {
+                let column = self.column(&name)?;
+                self.read();
+                self.expect_token_and_read(Token::Colon)?;
+                Statement::Column {
+                    columns: vec![column],
+                    statement: Box::new(self.parse_statement(Precedence::Prefix)?),
+                }
+            }
+            // Bare terms without a function are searched like contains, operators between them are statement operators
+            Token::WordOrPhrase(..)
+            | Token::Date(..)
+            | Token::Number(..)
+            | Token::ZeroToOne(..) => Statement::Contains {
+                expression: self.parse_expression(Precedence::Group)?,
+            },
+            t @ Token::Minus | t @ Token::Bang => {
+                self.expect_token_and_read(t.clone())?;
+                Statement::Prefix {
+                    operator: Operator::token(t),
+                    statement: Box::new(self.parse_statement(Precedence::Prefix)?),
+                }
+            }
+            // Groups of statements only change the precedence and leave no trace in the ast
+            Token::LeftParen => {
+                self.expect_token_and_read(Token::LeftParen)?;
+                let statement = self.parse_statement(Precedence::Lowest)?;
+                self.expect_token_and_read(Token::RightParen)?;
+                statement
+            }
+            // Macros are statements, so contains with only a macro as parameter is the macro itself
+            Token::Contains if self.is_contains_macro() => {
+                self.expect_token_and_read(Token::Contains)?;
+                self.expect_token_and_read(Token::Colon)?;
+                let name = match self.current.clone() {
+                    Token::Macro(name) => name,
+                    _ => return Err(ParseError::Unreachable),
+                };
+                self.read();
+                self.expect_token_and_read(Token::Colon)?;
+                Statement::Macro { name }
+            }
             Token::Contains => Statement::Contains {
                 expression: self.parse_contains()?,
             },
+            Token::Macro(name) => {
+                self.read();
+                Statement::Macro { name }
+            }
             Token::Starts => Statement::Starts {
                 expression: self.parse_starts()?,
             },
@@ -131,20 +286,63 @@ impl<'p> Parser<'p> {
                 expression: self.parse_thesaurus()?,
             },
             Token::Near => {
-                let (parameter, proximity) = self.parse_near()?;
+                let (parameter, proximity, ordered) = self.parse_near()?;
                 Statement::Near {
                     parameter,
                     proximity,
+                    ordered,
                 }
             }
             Token::Weighted => Statement::Weighted {
                 parameter: self.parse_weighted()?,
             },
+            Token::Freetext => {
+                let (text, language) = self.parse_freetext()?;
+                Statement::Freetext { text, language }
+            }
+            Token::Where => {
+                self.expect_token_and_read(Token::Where)?;
+                self.expect_token_and_read(Token::Colon)?;
+                let filter = self.parse_filter()?;
+                self.expect_token_and_read(Token::Colon)?;
+                filter
+            }
+            Token::Range => self.parse_range()?,
+            Token::Fuzzy => {
+                self.expect_token_and_read(Token::Fuzzy)?;
+                self.expect_token_and_read(Token::Colon)?;
+                let term = self.parse_fuzzy_term()?;
+                let mut distance = FUZZY_DISTANCE;
+                if self.current_is(Token::Comma) {
+                    self.expect_token_and_read(Token::Comma)?;
+                    distance = self.parse_fuzzy_distance()?;
+                }
+                self.expect_token_and_read(Token::Colon)?;
+                Statement::Fuzzy { term, distance }
+            }
+            Token::In => {
+                let (columns, statement) = self.parse_in()?;
+                Statement::Column {
+                    columns,
+                    statement: Box::new(statement),
+                }
+            }
+            Token::UnknownFunction(name) => {
+                let suggestion = match suggest_function(&name) {
+                    Some(function) => format!(" Did you mean {}?", function),
+                    None => String::new(),
+                };
+                return Err(ParseError::UnknownFunction(name, suggestion));
+            }
             _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
         };
-        // After a function could be an infix operator
-        while !self.current_is(Token::EoF) && precedence < Precedence::token(self.current.clone()) {
-            if let Some(in_statement) = self.parse_infix_statement(statement.clone())? {
+        // After a statement could be an infix operator or directly a new statement (here called postfix operator)
+        while !self.current_is(Token::EoF)
+            && precedence < Precedence::statement_token(self.current.clone())
+        {
+            if let Some(post_statement) = self.parse_postfix_statement(statement.clone())? {
+                statement = post_statement;
+            } else if let Some(in_statement) = self.parse_infix_statement(statement.clone())? {
                 statement = in_statement
             } else {
                 break;
@@ -168,6 +366,11 @@ impl<'p> Parser<'p> {
                 self.expect_token_and_read(Token::ZeroToOne(0.0))?;
                 Expression::ZeroToOne(f)
             }
+            // Dates are searched as phrase, MSSQL would otherwise split them at the minus
+            Token::Date(s) => {
+                self.expect_token_and_read(Token::Date(String::new()))?;
+                Expression::WordOrPhrase(format!("\"{}\"", s))
+            }
             t @ Token::Minus | t @ Token::Bang => {
                 self.expect_token_and_read(t.clone())?;
                 Expression::Prefix(
@@ -176,13 +379,10 @@ impl<'p> Parser<'p> {
                 )
             }
             // Start a group which gets higher precedence
-            Token::LeftParen => {
-                let group_expression = match self.parse_group()? {
-                    Statement::Group { expression } => expression,
-                    _ => return Err(ParseError::Unreachable),
-                };
-                group_expression
-            }
+            Token::LeftParen => match self.parse_group()? {
+                Statement::Group { expression } => expression,
+                _ => return Err(ParseError::Unreachable),
+            },
             _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
         };
         // Afer an expression could be an infix operator or directly a new expression (here called postfix operator)
@@ -205,7 +405,7 @@ impl<'p> Parser<'p> {
         expr: Expression,
     ) -> Result<Option<Expression>, ParseError> {
         Ok(match self.current {
-            Token::Minus | Token::Bang | Token::WordOrPhrase(..) => {
+            Token::Minus | Token::Bang | Token::WordOrPhrase(..) | Token::Date(..) => {
                 let sec_expr = self.parse_expression(Precedence::And)?;
                 Some(Expression::Infix(
                     Box::new(expr),
@@ -237,6 +437,42 @@ impl<'p> Parser<'p> {
         })
     }
 
+    // Postfix operator is called when two statements are read, automatically inserting an AND inbetween
+    // Second statement could be a bare term, a function, a group or have a NOT operator before it
+    fn parse_postfix_statement(
+        &mut self,
+        statement: Statement,
+    ) -> Result<Option<Statement>, ParseError> {
+        Ok(match self.current {
+            Token::WordOrPhrase(..)
+            | Token::Number(..)
+            | Token::ZeroToOne(..)
+            | Token::Minus
+            | Token::Bang
+            | Token::LeftParen
+            | Token::Contains
+            | Token::Starts
+            | Token::Inflection
+            | Token::Thesaurus
+            | Token::Near
+            | Token::Weighted
+            | Token::Freetext
+            | Token::In
+            | Token::Where
+            | Token::Range
+            | Token::Fuzzy
+            | Token::Macro(..) => {
+                let second_statement = self.parse_statement(Precedence::And)?;
+                Some(Statement::Infix {
+                    statement: Box::new(statement),
+                    operator: Operator::And,
+                    second_statement: Box::new(second_statement),
+                })
+            }
+            _ => None,
+        })
+    }
+
     // Infix operators AND and OR expect a statement on either side
     fn parse_infix_statement(
         &mut self,
@@ -295,63 +531,392 @@ impl<'p> Parser<'p> {
         Ok(expression)
     }
 
-    // Near function expects multiple comma-seperated words or phrases with an optional number as the last parameter
-    fn parse_near(&mut self) -> Result<(Vec<Expression>, Expression), ParseError> {
+    // Near function expects 2 to 64 comma-seperated terms with an optional distance as the last parameter
+    // Terms are words, phrases or prefix terms written as nested startswith, the distance is a number or MAX
+    // The last parameter can be followed by the word ordered to require the terms in the given order
+    fn parse_near(&mut self) -> Result<(Vec<Expression>, Expression, bool), ParseError> {
         self.expect_token_and_read(Token::Near)?;
         self.expect_token_and_read(Token::Colon)?;
         let mut parameter: Vec<Expression> = Vec::new();
         // Proximity has a default value of 5 if no number is given
         let mut proximity = Expression::Number(5);
+        let mut ordered = false;
         while !self.current_is(Token::Colon) {
             if self.current_is(Token::Comma) {
                 self.expect_token_and_read(Token::Comma)?;
             }
-            match self.parse_expression(Precedence::Lowest)? {
+            let expression = match self.current {
+                Token::Starts => match self.parse_starts()? {
+                    Expression::WordOrPhrase(s) => Expression::WordOrPhrase(prefix_term(s)),
+                    _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+                },
+                Token::Contains => self.parse_contains()?,
+                _ => self.parse_expression(Precedence::Lowest)?,
+            };
+            let last = self.current_is(Token::Colon) || self.is_ordered_flag();
+            match expression {
+                Expression::WordOrPhrase(s) if last && s.eq_ignore_ascii_case("max") => {
+                    proximity = Expression::WordOrPhrase(String::from("MAX"))
+                }
                 Expression::WordOrPhrase(s) => parameter.push(Expression::WordOrPhrase(s)),
-                Expression::Number(u) => {
-                    if self.current_is(Token::Colon) {
-                        proximity = Expression::Number(u)
-                    } else {
-                        return Err(ParseError::UnexpectedToken(self.current.clone()));
-                    }
+                Expression::Number(u) if last => proximity = Expression::Number(u),
+                // 0 and 1 are lexed as weights
+                Expression::ZeroToOne(f) if last && f.fract() == 0.0 => {
+                    proximity = Expression::Number(f as u64)
                 }
                 _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
             }
+            if last && self.current_is(Token::Comma) {
+                ordered = true;
+                self.expect_token_and_read(Token::Comma)?;
+                self.read();
+                self.expect_token(Token::Colon)?;
+            }
+        }
+        if !(NEAR_MIN_TERMS..=NEAR_MAX_TERMS).contains(&parameter.len()) {
+            return Err(ParseError::NearTermCount(parameter.len()));
         }
         self.expect_token_and_read(Token::Colon)?;
-        Ok((parameter, proximity))
+        Ok((parameter, proximity, ordered))
     }
 
-    // Weighted function expects pairs of words or phrases and a weight between 0 and 1
-    // All weights must add up to exactly 1
-    fn parse_weighted(&mut self) -> Result<Vec<(Expression, Expression)>, ParseError> {
+    // Weighted function expects pairs of terms and a weight between 0 and 1
+    // All weights must add up to 1, normalized weights can be any numbers and are scaled to add up to 1
+    fn parse_weighted(&mut self) -> Result<Vec<(Statement, Expression)>, ParseError> {
         self.expect_token_and_read(Token::Weighted)?;
         self.expect_token_and_read(Token::Colon)?;
-        let mut parameter: Vec<(Expression, Expression)> = Vec::new();
-        let mut sum_weights: f64 = 0.0;
+        let mut terms: Vec<Statement> = Vec::new();
+        let mut weights: Vec<f64> = Vec::new();
         while !self.current_is(Token::Colon) {
             if self.current_is(Token::Comma) {
                 self.expect_token_and_read(Token::Comma)?;
             }
-            let expression = match self.parse_expression(Precedence::Lowest)? {
-                Expression::WordOrPhrase(s) => Expression::WordOrPhrase(s),
-                _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
-            };
+            terms.push(self.parse_weighted_term()?);
             self.expect_token_and_read(Token::Comma)?;
             let weight = match self.parse_expression(Precedence::Lowest)? {
-                Expression::ZeroToOne(f) => {
-                    sum_weights += f;
-                    Expression::ZeroToOne(f)
-                }
+                Expression::ZeroToOne(f) => f,
+                Expression::Number(u) if self.options.normalize_weights => u as f64,
                 _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
             };
-            parameter.push((expression, weight));
+            weights.push(weight);
         }
-        if sum_weights != 1.0 {
+        let sum_weights: f64 = weights.iter().sum();
+        if self.options.normalize_weights && sum_weights > 0.0 {
+            weights.iter_mut().for_each(|weight| *weight /= sum_weights);
+        } else if (sum_weights - 1.0).abs() > WEIGHT_TOLERANCE {
             return Err(ParseError::WeightError(sum_weights));
         }
         self.expect_token_and_read(Token::Colon)?;
-        Ok(parameter)
+        Ok(terms
+            .into_iter()
+            .zip(weights.into_iter().map(Expression::ZeroToOne))
+            .collect())
+    }
+
+    // Weighted terms are words or phrases, which are stored as contains
+    // Functions of a single word or phrase and near functions are weighted terms aswell
+    fn parse_weighted_term(&mut self) -> Result<Statement, ParseError> {
+        let statement = match self.current {
+            Token::Near => {
+                let (parameter, proximity, ordered) = self.parse_near()?;
+                return Ok(Statement::Near {
+                    parameter,
+                    proximity,
+                    ordered,
+                });
+            }
+            Token::Contains => Statement::Contains {
+                expression: self.parse_contains()?,
+            },
+            Token::Starts => Statement::Starts {
+                expression: self.parse_starts()?,
+            },
+            Token::Inflection => Statement::Inflection {
+                expression: self.parse_inflection()?,
+            },
+            Token::Thesaurus => Statement::Thesaurus {
+                expression: self.parse_thesaurus()?,
+            },
+            _ => Statement::Contains {
+                expression: self.parse_expression(Precedence::Lowest)?,
+            },
+        };
+        match statement {
+            Statement::Contains {
+                expression: Expression::WordOrPhrase(..),
+            }
+            | Statement::Starts {
+                expression: Expression::WordOrPhrase(..),
+            }
+            | Statement::Inflection {
+                expression: Expression::WordOrPhrase(..),
+            }
+            | Statement::Thesaurus {
+                expression: Expression::WordOrPhrase(..),
+            } => Ok(statement),
+            _ => Err(ParseError::UnexpectedToken(self.current.clone())),
+        }
+    }
+
+    // Freetext function expects natural language text and optionally a language name or id after a comma
+    // Operators have no meaning in natural language and are left out of the text
+    fn parse_freetext(&mut self) -> Result<(String, Option<Expression>), ParseError> {
+        self.expect_token_and_read(Token::Freetext)?;
+        self.expect_token_and_read(Token::Colon)?;
+        let mut words: Vec<String> = Vec::new();
+        while !self.current_is(Token::Colon) && !self.current_is(Token::Comma) {
+            match self.current.clone() {
+                Token::WordOrPhrase(s) | Token::Date(s) => words.push(s),
+                Token::Number(u) => words.push(u.to_string()),
+                Token::ZeroToOne(f) => words.push(f.to_string()),
+                _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+            }
+            self.read();
+        }
+        if words.is_empty() {
+            return Err(ParseError::UnexpectedToken(self.current.clone()));
+        }
+        let mut language = None;
+        if self.current_is(Token::Comma) {
+            self.expect_token_and_read(Token::Comma)?;
+            language = Some(self.parse_language()?);
+        }
+        self.expect_token_and_read(Token::Colon)?;
+        Ok((words.join(" "), language))
+    }
+
+    // Whether the current contains function has a single macro as parameter, e.g. @contains:$name:
+    fn is_contains_macro(&self) -> bool {
+        let mut tokens = self.tokens.clone();
+        self.peek == Token::Colon
+            && matches!(
+                (tokens.next(), tokens.next()),
+                (Some(Token::Macro(..)), Some(Token::Colon))
+            )
+    }
+
+    // Fuzzy terms are single words without wildcards, phrases are matched exactly
+    fn parse_fuzzy_term(&mut self) -> Result<Expression, ParseError> {
+        match self.current.clone() {
+            Token::WordOrPhrase(word) if !word.starts_with('"') && !word.contains(['*', '?']) => {
+                self.read();
+                Ok(Expression::WordOrPhrase(word))
+            }
+            _ => Err(ParseError::UnexpectedToken(self.current.clone())),
+        }
+    }
+
+    // Edit distance of a fuzzy term
+    fn parse_fuzzy_distance(&mut self) -> Result<u64, ParseError> {
+        let distance = self
+            .integer()
+            .ok_or_else(|| ParseError::UnexpectedToken(self.current.clone()))?;
+        if distance > FUZZY_MAX_DISTANCE {
+            return Err(ParseError::FuzzyDistance(distance));
+        }
+        self.read();
+        Ok(distance)
+    }
+
+    // Sort option expects a column and optionally the order after a comma
+    fn parse_sort(&mut self) -> Result<Statement, ParseError> {
+        self.expect_token_and_read(Token::Sort)?;
+        self.expect_token_and_read(Token::Colon)?;
+        let column = match self.current.clone() {
+            Token::WordOrPhrase(name) => self.options.sort_column(&name).ok_or_else(|| {
+                ParseError::UnknownSortColumn(name, self.options.sort_columns().join(", "))
+            })?,
+            _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+        };
+        self.read();
+        let mut order = default_order(&column);
+        if self.current_is(Token::Comma) {
+            self.expect_token_and_read(Token::Comma)?;
+            order = match &self.current {
+                Token::WordOrPhrase(name) => name
+                    .parse::<Order>()
+                    .map_err(|_| ParseError::UnexpectedToken(self.current.clone()))?,
+                _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+            };
+            self.read();
+        }
+        self.expect_token_and_read(Token::Colon)?;
+        Ok(Statement::Sort { column, order })
+    }
+
+    // Languages are given by their name, code or locale identifier
+    fn parse_language(&mut self) -> Result<Expression, ParseError> {
+        match self.parse_expression(Precedence::Lowest)? {
+            expression @ Expression::WordOrPhrase(..) | expression @ Expression::Number(..) => {
+                Ok(expression)
+            }
+            _ => Err(ParseError::UnexpectedToken(self.current.clone())),
+        }
+    }
+
+    // In function expects comma-separated column names and a statement which is only searched in those columns
+    fn parse_in(&mut self) -> Result<(Vec<String>, Statement), ParseError> {
+        self.expect_token_and_read(Token::In)?;
+        self.expect_token_and_read(Token::Colon)?;
+        let mut columns: Vec<String> = Vec::new();
+        while !self.current_is(Token::Colon) || columns.is_empty() {
+            if self.current_is(Token::Comma) {
+                self.expect_token_and_read(Token::Comma)?;
+            }
+            match self.current.clone() {
+                Token::WordOrPhrase(name) => columns.push(self.column(&name)?),
+                _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+            }
+            self.read();
+        }
+        self.expect_token_and_read(Token::Colon)?;
+        let in_columns = std::mem::replace(&mut self.in_columns, true);
+        let statement = self.parse_statement(Precedence::Lowest);
+        self.in_columns = in_columns;
+        let statement = statement?;
+        self.expect_token_and_read(Token::Colon)?;
+        Ok((columns, statement))
+    }
+
+    // Columns must be one of the configured columns, they are compared case-insensitive
+    // Input: column name
+    // Output: column name as configured or error
+    fn column(&self, name: &str) -> Result<String, ParseError> {
+        self.options
+            .columns
+            .iter()
+            .find(|column| column.eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| {
+                ParseError::UnknownColumn(name.to_owned(), self.options.columns.join(", "))
+            })
+    }
+
+    // Filters compare a configured metadata field with a value of the field's type
+    fn parse_filter(&mut self) -> Result<Statement, ParseError> {
+        let (field, field_type) = self.parse_field()?;
+        let comparison = match self.current.clone() {
+            Token::Comparison(comparison) => comparison,
+            _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+        };
+        self.read();
+        let value = self.parse_filter_value(&field, field_type)?;
+        Ok(Statement::Filter {
+            field,
+            comparison,
+            value,
+        })
+    }
+
+    // Range function expects a field, its lower and its upper bound separated by commas
+    fn parse_range(&mut self) -> Result<Statement, ParseError> {
+        self.expect_token_and_read(Token::Range)?;
+        self.expect_token_and_read(Token::Colon)?;
+        let (field, field_type) = self.parse_field()?;
+        self.expect_token_and_read(Token::Comma)?;
+        let from = self.parse_filter_value(&field, field_type)?;
+        self.expect_token_and_read(Token::Comma)?;
+        let to = self.parse_filter_value(&field, field_type)?;
+        self.expect_token_and_read(Token::Colon)?;
+        range(field, from, to)
+    }
+
+    // Ranges can also be written as field followed by a colon and both bounds in brackets separated by TO
+    fn parse_bounds(&mut self) -> Result<Statement, ParseError> {
+        let (field, field_type) = self.parse_field()?;
+        self.expect_token_and_read(Token::Colon)?;
+        self.expect_token_and_read(Token::LeftBracket)?;
+        let from = self.parse_filter_value(&field, field_type)?;
+        match &self.current {
+            Token::WordOrPhrase(to) if to.eq_ignore_ascii_case("to") => self.read(),
+            _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+        }
+        let to = self.parse_filter_value(&field, field_type)?;
+        self.expect_token_and_read(Token::RightBracket)?;
+        range(field, from, to)
+    }
+
+    // Fields must be one of the configured filters, they are compared case-insensitive
+    // Output: field name as configured and its type or error
+    fn parse_field(&mut self) -> Result<(String, FieldType), ParseError> {
+        let name = match self.current.clone() {
+            Token::WordOrPhrase(name) => name,
+            _ => return Err(ParseError::UnexpectedToken(self.current.clone())),
+        };
+        let field = self
+            .options
+            .filters
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(&name))
+            .cloned()
+            .ok_or_else(|| {
+                let fields: Vec<&str> = self
+                    .options
+                    .filters
+                    .iter()
+                    .map(|(field, _)| field.as_str())
+                    .collect();
+                ParseError::UnknownField(name, fields.join(", "))
+            })?;
+        self.read();
+        Ok(field)
+    }
+
+    // Filter values must match the type of their field
+    fn parse_filter_value(
+        &mut self,
+        field: &str,
+        field_type: FieldType,
+    ) -> Result<FilterValue, ParseError> {
+        let invalid = || ParseError::FilterValue(field.to_owned(), field_type);
+        let value = match (field_type, self.current.clone()) {
+            (FieldType::Text, Token::WordOrPhrase(s)) => FilterValue::Text(unquote(&s)),
+            (FieldType::Text, Token::Date(s)) => FilterValue::Text(s),
+            (FieldType::Text, Token::Number(u)) => FilterValue::Text(u.to_string()),
+            (FieldType::Text, Token::ZeroToOne(f)) => FilterValue::Text(f.to_string()),
+            (FieldType::Integer, _) => FilterValue::Integer(self.integer().ok_or_else(invalid)?),
+            (FieldType::Date, _) => FilterValue::Date(self.date().ok_or_else(invalid)?),
+            _ => return Err(invalid()),
+        };
+        self.read();
+        Ok(value)
+    }
+
+    // Integer value of the current token, a single 1 or 0 is lexed as a weight
+    fn integer(&self) -> Option<u64> {
+        match self.current {
+            Token::Number(u) => Some(u),
+            Token::ZeroToOne(f) if f.fract() == 0.0 => Some(f as u64),
+            _ => None,
+        }
+    }
+
+    // Dates are written as YYYY-MM-DD, either quoted or as numbers separated by minus
+    // The last part of the date is left as current token
+    // Output: date in the format YYYY-MM-DD, none if it is no valid date
+    fn date(&mut self) -> Option<String> {
+        let parts: Vec<u64> = match self.current.clone() {
+            Token::WordOrPhrase(s) | Token::Date(s) => s
+                .trim_matches('"')
+                .split('-')
+                .map(|part| part.parse::<u64>().ok())
+                .collect::<Option<Vec<u64>>>()?,
+            _ => {
+                let mut parts = vec![self.integer()?];
+                while parts.len() < 3 && self.peek == Token::Minus {
+                    self.read();
+                    self.read();
+                    parts.push(self.integer()?);
+                }
+                parts
+            }
+        };
+        match parts.as_slice() {
+            [year, month @ 1..=12, day] if (1..=days_in_month(*year, *month)).contains(day) => {
+                Some(format!("{:04}-{:02}-{:02}", year, month, day))
+            }
+            _ => None,
+        }
     }
 
     // Groups must encapsulate an expression with parentheses and have higher precedence then other operators
@@ -361,6 +926,75 @@ impl<'p> Parser<'p> {
         self.expect_token_and_read(Token::RightParen)?;
         Ok(Statement::Group { expression })
     }
+
+    // Ordered is only the flag of near if it is the last parameter, otherwise it is a term
+    fn is_ordered_flag(&self) -> bool {
+        self.current_is(Token::Comma)
+            && is_ordered(&self.peek)
+            && self.tokens.clone().next() == Some(&Token::Colon)
+    }
+}
+
+// Turn a word or phrase into a quoted prefix term, a phrase gets the * before its closing "
+fn prefix_term(word_or_phrase: String) -> String {
+    match word_or_phrase.strip_suffix('"') {
+        Some(phrase) if word_or_phrase.starts_with('"') => format!("{}*\"", phrase),
+        _ => format!("\"{}*\"", word_or_phrase),
+    }
+}
+
+// Text of a filter value, phrases lose their quotes and escaped quotes and backslashes are unescaped
+// Example: "a \"b\"" becomes a "b"
+fn unquote(word_or_phrase: &str) -> String {
+    let phrase = match word_or_phrase
+        .strip_prefix('"')
+        .and_then(|phrase| phrase.strip_suffix('"'))
+    {
+        Some(phrase) => phrase,
+        None => return word_or_phrase.to_owned(),
+    };
+    let mut text = String::with_capacity(phrase.len());
+    let mut chars = phrase.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('"' | '\\')) => text.extend(chars.next()),
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+// The word ordered as last parameter of near, case-insensitive
+fn is_ordered(token: &Token) -> bool {
+    matches!(token, Token::WordOrPhrase(s) if s.eq_ignore_ascii_case("ordered"))
+}
+
+// Number of days of a month, february has 29 days in leap years of the gregorian calendar
+fn days_in_month(year: u64, month: u64) -> u64 {
+    // Leap years are divisible by 4, centuries only if they are divisible by 400
+    match (month, year % 4, year % 100, year % 400) {
+        (2, 0, 1.., _) | (2, _, _, 0) => 29,
+        (2, ..) => 28,
+        (4 | 6 | 9 | 11, ..) => 30,
+        _ => 31,
+    }
+}
+
+// Ranges must start with their lower bound, bounds of the same type are compared
+// Input: field name and bounds
+// Output: range statement or error
+fn range(field: String, from: FilterValue, to: FilterValue) -> Result<Statement, ParseError> {
+    let ordered = match (&from, &to) {
+        (FilterValue::Integer(from), FilterValue::Integer(to)) => from <= to,
+        (FilterValue::Text(from), FilterValue::Text(to))
+        | (FilterValue::Date(from), FilterValue::Date(to)) => from <= to,
+        _ => false,
+    };
+    if ordered {
+        Ok(Statement::Range { field, from, to })
+    } else {
+        Err(ParseError::RangeOrder(field, from, to))
+    }
 }
 
 // Types of errors covered by the parser
@@ -372,4 +1006,343 @@ pub enum ParseError {
     Unreachable,
     #[error("Weights do not add up to 1.0. Sum of all weights: {0}")]
     WeightError(f64),
+    #[error("Near expects between 2 and 64 terms, found {0}.")]
+    NearTermCount(usize),
+    #[error("Unknown column {0}, allowed columns: {1}.")]
+    UnknownColumn(String, String),
+    #[error("Unknown field {0}, allowed fields: {1}.")]
+    UnknownField(String, String),
+    #[error("Invalid value for {0}, expected {1}.")]
+    FilterValue(String, FieldType),
+    #[error("Range of {0} must start with the lower bound, found {1} to {2}.")]
+    RangeOrder(String, FilterValue, FilterValue),
+    #[error("Results cannot be sorted by {0}, allowed columns: {1}.")]
+    UnknownSortColumn(String, String),
+    #[error("Fuzzy terms allow an edit distance of at most 2, found {0}.")]
+    FuzzyDistance(u64),
+    #[error("Unknown function {0}.{1}")]
+    UnknownFunction(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_gen::lexer::lex;
+    use crate::code_gen::options::Options;
+
+    fn parse_query(query: &str) -> Result<Vec<Statement>, ParseError> {
+        parse(lex(query))
+    }
+
+    fn word(word: &str) -> Expression {
+        Expression::WordOrPhrase(word.to_owned())
+    }
+
+    fn contains(term: &str) -> Statement {
+        Statement::Contains {
+            expression: word(term),
+        }
+    }
+
+    fn infix(statement: Statement, operator: Operator, second_statement: Statement) -> Statement {
+        Statement::Infix {
+            statement: Box::new(statement),
+            operator,
+            second_statement: Box::new(second_statement),
+        }
+    }
+
+    fn same_ast(query: &str, other: &str) {
+        assert_eq!(parse_query(query).unwrap(), parse_query(other).unwrap());
+    }
+
+    #[test]
+    fn reads_ordered_as_flag_only_as_last_parameter() {
+        let near = |query: &str| match parse_query(query).unwrap().as_slice() {
+            [Statement::Near {
+                parameter, ordered, ..
+            }] => (parameter.len(), *ordered),
+            ast => panic!("{:?}", ast),
+        };
+        assert_eq!(near("@near:a,b,ordered:"), (2, true));
+        assert_eq!(near("@near:a,b,3,ordered:"), (2, true));
+        assert_eq!(near("@near:a,ordered,b:"), (3, false));
+    }
+
+    #[test]
+    fn rejects_days_after_the_end_of_the_month() {
+        for date in ["2020-02-30", "2021-02-29", "1900-02-29", "2020-04-31"] {
+            let query = format!("modified:[{} TO 2030-01-01] & rust", date);
+            assert!(
+                matches!(parse_query(&query), Err(ParseError::FilterValue(..))),
+                "{}",
+                date
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_leap_days() {
+        for date in ["2020-02-29", "2000-02-29"] {
+            let query = format!("@range:modified,{},2030-01-01: & rust", date);
+            assert!(parse_query(&query).is_ok(), "{}", date);
+        }
+    }
+
+    #[test]
+    fn parses_negated_and_grouped_statements() {
+        assert_eq!(
+            parse_query("!@contains:rust: & @contains:go:").unwrap(),
+            vec![infix(
+                Statement::Prefix {
+                    operator: Operator::Not,
+                    statement: Box::new(contains("rust")),
+                },
+                Operator::And,
+                contains("go"),
+            )]
+        );
+        assert_eq!(
+            parse_query("(@contains:a: | @contains:b:) & @contains:c:").unwrap(),
+            vec![infix(
+                infix(contains("a"), Operator::Or, contains("b")),
+                Operator::And,
+                contains("c"),
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_bare_terms_as_contains() {
+        same_ast("rust", "@contains:rust:");
+        assert_eq!(
+            parse_query("rust go").unwrap(),
+            vec![infix(contains("rust"), Operator::And, contains("go"))]
+        );
+        assert_eq!(
+            parse_query("rust | \"rust lang\"").unwrap(),
+            vec![infix(
+                contains("rust"),
+                Operator::Or,
+                contains("\"rust lang\"")
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_keyword_operators() {
+        same_ast("rust AND NOT go OR java", "rust & !go | java");
+        let options = Options {
+            keyword_operators: false,
+            ..Options::default()
+        };
+        let tokens = crate::code_gen::lexer::lex_with_options("rust AND go", &options);
+        assert_eq!(
+            parse(tokens).unwrap(),
+            vec![infix(
+                infix(contains("rust"), Operator::And, contains("AND")),
+                Operator::And,
+                contains("go"),
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_function_aliases_case_insensitive() {
+        same_ast("@C:rust:", "@contains:rust:");
+        same_ast("@SW:ru:", "@startswith:ru:");
+        same_ast("@Inf:run:", "@inflection:run:");
+        same_ast("@syn:car:", "@thesaurus:car:");
+        same_ast("@N:a,b:", "@near:a,b:");
+        same_ast("@W:a,0.5,b,0.5:", "@weighted:a,0.5,b,0.5:");
+        same_ast("@FT:rust:", "@freetext:rust:");
+        assert!(matches!(
+            parse_query("@contians:rust:"),
+            Err(ParseError::UnknownFunction(name, suggestion))
+                if name == "@contians" && suggestion == " Did you mean @contains?"
+        ));
+    }
+
+    #[test]
+    fn parses_near_distances_and_prefix_terms() {
+        assert_eq!(
+            parse_query("@near:a,@startswith:ru:,3:").unwrap(),
+            vec![Statement::Near {
+                parameter: vec![word("a"), word("\"ru*\"")],
+                proximity: Expression::Number(3),
+                ordered: false,
+            }]
+        );
+        assert!(matches!(
+            parse_query("@near:a,b,max:").unwrap().as_slice(),
+            [Statement::Near { proximity, .. }] if *proximity == word("MAX")
+        ));
+        assert!(matches!(
+            parse_query("@near:a:"),
+            Err(ParseError::NearTermCount(1))
+        ));
+    }
+
+    #[test]
+    fn parses_weights_with_tolerance_and_normalization() {
+        assert!(parse_query("@weighted:a,0.1,b,0.2,c,0.7:").is_ok());
+        assert!(matches!(
+            parse_query("@weighted:a,0.3,b,0.3:"),
+            Err(ParseError::WeightError(_))
+        ));
+        let options = Options {
+            normalize_weights: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            parse_with_options(lex("@weighted:a,2,b,6:"), &options).unwrap(),
+            vec![Statement::Weighted {
+                parameter: vec![
+                    (contains("a"), Expression::ZeroToOne(0.25)),
+                    (contains("b"), Expression::ZeroToOne(0.75)),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_freetext_as_literal_text() {
+        assert_eq!(
+            parse_query("@freetext:rust & (language),de:").unwrap(),
+            vec![Statement::Freetext {
+                text: String::from("rust & (language)"),
+                language: Some(word("de")),
+            }]
+        );
+        for (query, text) in [
+            ("@freetext:rock and roll:", "rock and roll"),
+            ("@freetext:not good:", "not good"),
+            (
+                "@freetext:-good !bad rock&roll 007:",
+                "-good !bad rock&roll 007",
+            ),
+        ] {
+            assert_eq!(
+                parse_query(query).unwrap(),
+                vec![Statement::Freetext {
+                    text: String::from(text),
+                    language: None,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn parses_column_scopes() {
+        let expected = vec![Statement::Column {
+            columns: vec![String::from("Title")],
+            statement: Box::new(contains("rust")),
+        }];
+        assert_eq!(parse_query("@in:title:rust:").unwrap(), expected);
+        assert_eq!(parse_query("title:rust").unwrap(), expected);
+        assert!(matches!(
+            parse_query("body:rust"),
+            Err(ParseError::UnknownColumn(..))
+        ));
+    }
+
+    #[test]
+    fn parses_language_and_sort_statements() {
+        assert_eq!(
+            parse_query("@lang:de: @sort:size,desc: rust").unwrap(),
+            vec![
+                Statement::Language {
+                    language: word("de"),
+                },
+                Statement::Sort {
+                    column: String::from("Size"),
+                    order: Order::Descending,
+                },
+                contains("rust"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_filters_and_ranges() {
+        assert_eq!(
+            parse_query("rust size>5").unwrap(),
+            vec![infix(
+                contains("rust"),
+                Operator::And,
+                Statement::Filter {
+                    field: String::from("Size"),
+                    comparison: Comparison::Greater,
+                    value: FilterValue::Integer(5),
+                },
+            )]
+        );
+        same_ast("rust size>5", "rust @where:size>5:");
+        same_ast("rust size:[1 TO 10]", "rust @range:size,1,10:");
+        assert!(matches!(
+            parse_query("rust size>big"),
+            Err(ParseError::FilterValue(..))
+        ));
+        assert!(matches!(
+            parse_query("rust @where:pages>5:"),
+            Err(ParseError::UnknownField(..))
+        ));
+    }
+
+    #[test]
+    fn parses_spaced_comparisons_and_quoted_text() {
+        same_ast("rust year >= 2010", "rust year>=2010");
+        same_ast("rust year>= 2010", "rust year>=2010");
+        same_ast("rust year >=2010", "rust year>=2010");
+        same_ast("rust category = Physics", "rust category=Physics");
+        same_ast("rust category != Physics", "rust category!=Physics");
+        assert_eq!(
+            parse_query(r#"rust category="a \"b\"""#).unwrap(),
+            vec![infix(
+                contains("rust"),
+                Operator::And,
+                Statement::Filter {
+                    field: String::from("Category"),
+                    comparison: Comparison::Equal,
+                    value: FilterValue::Text(String::from(r#"a "b""#)),
+                },
+            )]
+        );
+    }
+
+    #[test]
+    fn parses_fuzzy_terms() {
+        assert_eq!(
+            parse_query("rust~1").unwrap(),
+            vec![Statement::Fuzzy {
+                term: word("rust"),
+                distance: 1,
+            }]
+        );
+        same_ast("rust~", "@fuzzy:rust,2:");
+        assert!(matches!(
+            parse_query("@fuzzy:rust,3:"),
+            Err(ParseError::FuzzyDistance(3))
+        ));
+    }
+
+    #[test]
+    fn parses_macro_definitions() {
+        assert_eq!(
+            parse_query("$x = rust | go; $x & lang").unwrap(),
+            vec![
+                Statement::Define {
+                    name: String::from("x"),
+                    statement: Box::new(infix(contains("rust"), Operator::Or, contains("go"))),
+                },
+                infix(
+                    Statement::Macro {
+                        name: String::from("x"),
+                    },
+                    Operator::And,
+                    contains("lang"),
+                ),
+            ]
+        );
+    }
 }
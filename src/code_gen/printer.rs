@@ -0,0 +1,487 @@
+use std::fmt;
+
+use crate::code_gen::ast::{Comparison, Expression, FilterValue, Operator, Statement};
+
+// Formatting constants
+const LINE_WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+// Main function to start the printing process
+// Input: abstract syntax tree (vec of statements)
+// Output: canonical query string that parses back to the same ast
+pub fn print(ast: &[Statement]) -> String {
+    ast.iter()
+        .map(|statement| statement.to_string())
+        .filter(|query| !query.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Formats a query over multiple lines, statements that fit into one line are kept as they are
+// Input: abstract syntax tree (vec of statements)
+// Output: formatted query string, one statement per line, that parses back to the same ast
+pub fn format(ast: &[Statement]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for statement in ast {
+        if *statement != Statement::EoF {
+            lines.append(&mut format_statement(statement, 0));
+        }
+    }
+    lines.join("\n")
+}
+
+// Format a statement, the first line is not indented as the caller might prefix it with an operator
+// Input: statement and indentation depth of all following lines
+// Output: vec of lines
+fn format_statement(statement: &Statement, depth: usize) -> Vec<String> {
+    let single_line = statement.to_string();
+    if INDENT.len() * depth + single_line.chars().count() <= LINE_WIDTH {
+        return vec![single_line];
+    }
+    let indent = INDENT.repeat(depth);
+    let parameter_indent = INDENT.repeat(depth + 1);
+    match statement {
+        // The second statement starts on a new line behind the operator
+        Statement::Infix {
+            statement,
+            operator,
+            second_statement,
+        } => {
+            let mut lines = format_operand(statement, operator, depth, false);
+            let mut second_lines = format_operand(second_statement, operator, depth, true);
+            second_lines[0] = format!("{}{} {}", indent, operator, second_lines[0]);
+            lines.append(&mut second_lines);
+            lines
+        }
+        // Negated infix statements keep their parentheses around all lines and are indented as a nested group
+        Statement::Prefix {
+            operator,
+            statement,
+        } => {
+            let nested = match **statement {
+                Statement::Infix { .. } => depth + 1,
+                _ => depth,
+            };
+            let mut lines = format_statement(statement, nested);
+            if let Statement::Infix { .. } = **statement {
+                lines[0].insert(0, '(');
+                if let Some(last) = lines.last_mut() {
+                    last.push(')');
+                }
+            }
+            lines[0] = format!("{}{}", operator, lines[0]);
+            lines
+        }
+        // Near and weighted put each parameter on its own line
+        Statement::Near {
+            parameter,
+            proximity,
+            ordered,
+        } => {
+            let mut lines = vec![String::from("@near:")];
+            for expression in parameter {
+                lines.push(format!("{}{},", parameter_indent, expression));
+            }
+            if *ordered {
+                lines.push(format!("{}{},", parameter_indent, proximity));
+                lines.push(format!("{}ordered:", parameter_indent));
+            } else {
+                lines.push(format!("{}{}:", parameter_indent, proximity));
+            }
+            lines
+        }
+        Statement::Weighted { parameter } => {
+            let mut lines = vec![String::from("@weighted:")];
+            for (term, weight) in parameter {
+                lines.push(format!(
+                    "{}{},{},",
+                    parameter_indent,
+                    weighted_term(term),
+                    weight
+                ));
+            }
+            if let Some(last) = lines.last_mut() {
+                last.pop();
+                last.push(':');
+            }
+            lines
+        }
+        // Column scopes put their statement on the following lines and close on their own line
+        Statement::Column { columns, statement } => {
+            let mut lines = vec![format!("@in:{}:", columns.join(","))];
+            let mut inner = format_statement(statement, depth + 1);
+            inner[0].insert_str(0, &parameter_indent);
+            lines.append(&mut inner);
+            lines.push(format!("{}:", indent));
+            lines
+        }
+        // Macro definitions put their statement on the following lines, the semicolon ends the last line
+        Statement::Define { name, statement } => {
+            let mut lines = vec![format!("${} =", name)];
+            let mut inner = format_statement(statement, depth + 1);
+            inner[0].insert_str(0, &parameter_indent);
+            lines.append(&mut inner);
+            if let Some(last) = lines.last_mut() {
+                last.push(';');
+            }
+            lines
+        }
+        _ => vec![single_line],
+    }
+}
+
+// Format an operand of an infix statement
+// Chains of the same operator stay on one level, any other nested infix statement is indented further
+fn format_operand(
+    statement: &Statement,
+    operator: &Operator,
+    depth: usize,
+    right: bool,
+) -> Vec<String> {
+    let inner = infix_statement_operator(statement);
+    let depth = match inner {
+        Some(inner) if inner != operator => depth + 1,
+        _ => depth,
+    };
+    let mut lines = format_statement(statement, depth);
+    if needs_parentheses(inner, operator, right) {
+        lines[0].insert(0, '(');
+        if let Some(last) = lines.last_mut() {
+            last.push(')');
+        }
+    }
+    lines
+}
+
+// Weighted terms without a function are printed without contains
+fn weighted_term(term: &Statement) -> String {
+    match term {
+        Statement::Contains { expression } => expression.to_string(),
+        _ => term.to_string(),
+    }
+}
+
+// Binding strength of infix operators, mirrors the precedences used by the parser
+fn strength(operator: &Operator) -> u8 {
+    match operator {
+        Operator::Or => 1,
+        Operator::And => 2,
+        Operator::Not => 3,
+    }
+}
+
+// Operands need parentheses only if the parser would otherwise regroup them
+// Operators are left associative, so an equally strong operator on the right side needs parentheses too
+fn needs_parentheses(
+    operand_operator: Option<&Operator>,
+    operator: &Operator,
+    right: bool,
+) -> bool {
+    match operand_operator {
+        Some(inner) if right => strength(inner) <= strength(operator),
+        Some(inner) => strength(inner) < strength(operator),
+        None => false,
+    }
+}
+
+// Write an operand of an infix operator
+fn write_operand<T: fmt::Display>(
+    f: &mut fmt::Formatter,
+    operand: &T,
+    operand_operator: Option<&Operator>,
+    operator: &Operator,
+    right: bool,
+) -> fmt::Result {
+    if needs_parentheses(operand_operator, operator, right) {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+// Operator of an expression if it is an infix expression
+fn infix_operator(expression: &Expression) -> Option<&Operator> {
+    match expression {
+        Expression::Infix(_, operator, _) => Some(operator),
+        _ => None,
+    }
+}
+
+// Operator of a statement if it is an infix statement
+fn infix_statement_operator(statement: &Statement) -> Option<&Operator> {
+    match statement {
+        Statement::Infix { operator, .. } => Some(operator),
+        _ => None,
+    }
+}
+
+// Operators are always printed with their symbol, implicit ANDs become explicit
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::And => write!(f, "&"),
+            Operator::Or => write!(f, "|"),
+            Operator::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Comparison::Equal => write!(f, "="),
+            Comparison::NotEqual => write!(f, "!="),
+            Comparison::Less => write!(f, "<"),
+            Comparison::LessEqual => write!(f, "<="),
+            Comparison::Greater => write!(f, ">"),
+            Comparison::GreaterEqual => write!(f, ">="),
+        }
+    }
+}
+
+// Text values are always quoted, so they are never read as numbers, operators or functions
+impl fmt::Display for FilterValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            // Quotes and backslashes are escaped as the parser unescapes them
+            FilterValue::Text(s) => {
+                write!(f, "\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            FilterValue::Integer(u) => write!(f, "{}", u),
+            FilterValue::Date(date) => write!(f, "{}", date),
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::WordOrPhrase(s) => write!(f, "{}", s),
+            // A single 1 is lexed as a weight, the leading zero keeps it a number
+            Expression::Number(1) => write!(f, "01"),
+            Expression::Number(u) => write!(f, "{}", u),
+            Expression::ZeroToOne(float) => write!(f, "{}", float),
+            Expression::Infix(expr1, operator, expr2) => {
+                write_operand(f, expr1, infix_operator(expr1), operator, false)?;
+                write!(f, " {} ", operator)?;
+                write_operand(f, expr2, infix_operator(expr2), operator, true)
+            }
+            // Prefix operators bind stronger than any infix operator
+            Expression::Prefix(operator, expr) => match **expr {
+                Expression::Infix(..) => write!(f, "{}({})", operator, expr),
+                _ => write!(f, "{}{}", operator, expr),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::Group { expression } => write!(f, "({})", expression),
+            Statement::Infix {
+                statement,
+                operator,
+                second_statement,
+            } => {
+                let first_operator = infix_statement_operator(statement);
+                let second_operator = infix_statement_operator(second_statement);
+                write_operand(f, statement, first_operator, operator, false)?;
+                write!(f, " {} ", operator)?;
+                write_operand(f, second_statement, second_operator, operator, true)
+            }
+            Statement::Prefix {
+                operator,
+                statement,
+            } => match **statement {
+                Statement::Infix { .. } => write!(f, "{}({})", operator, statement),
+                _ => write!(f, "{}{}", operator, statement),
+            },
+            Statement::Contains { expression } => write!(f, "@contains:{}:", expression),
+            Statement::Starts { expression } => write!(f, "@startswith:{}:", expression),
+            Statement::Inflection { expression } => write!(f, "@inflection:{}:", expression),
+            Statement::Thesaurus { expression } => write!(f, "@thesaurus:{}:", expression),
+            // The proximity is always printed, even if it is the default value
+            Statement::Near {
+                parameter,
+                proximity,
+                ordered,
+            } => {
+                write!(f, "@near:")?;
+                for expression in parameter {
+                    write!(f, "{},", expression)?;
+                }
+                if *ordered {
+                    write!(f, "{},ordered:", proximity)
+                } else {
+                    write!(f, "{}:", proximity)
+                }
+            }
+            Statement::Column { columns, statement } => {
+                write!(f, "@in:{}:{}:", columns.join(","), statement)
+            }
+            Statement::Language { language } => write!(f, "@lang:{}:", language),
+            Statement::Filter {
+                field,
+                comparison,
+                value,
+            } => write!(f, "@where:{}{}{}:", field, comparison, value),
+            Statement::Fuzzy { term, distance } => write!(f, "@fuzzy:{},{}:", term, distance),
+            Statement::Define { name, statement } => write!(f, "${} = {};", name, statement),
+            Statement::Macro { name } => write!(f, "${}", name),
+            Statement::Sort { column, order } => write!(f, "@sort:{},{}:", column, order),
+            Statement::Range { field, from, to } => {
+                write!(f, "@range:{},{},{}:", field, from, to)
+            }
+            Statement::Freetext { text, language } => match language {
+                Some(language) => write!(f, "@freetext:{},{}:", text, language),
+                None => write!(f, "@freetext:{}:", text),
+            },
+            Statement::Weighted { parameter } => {
+                let pairs: Vec<String> = parameter
+                    .iter()
+                    .map(|(term, weight)| format!("{},{}", weighted_term(term), weight))
+                    .collect();
+                write!(f, "@weighted:{}:", pairs.join(","))
+            }
+            Statement::EoF => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_gen::lexer::lex;
+    use crate::code_gen::parser::parse;
+
+    fn parse_query(query: &str) -> Vec<Statement> {
+        parse(lex(query)).unwrap_or_else(|error| panic!("{}: {}", query, error))
+    }
+
+    // Formatted queries parse back to the same ast and every line is indented as deep as its nesting
+    fn assert_formats(query: &str, expected: &[&str]) {
+        let ast = parse_query(query);
+        let formatted = format(&ast);
+        assert_eq!(formatted.lines().collect::<Vec<&str>>(), expected);
+        assert_eq!(parse_query(&formatted), ast);
+    }
+
+    #[test]
+    fn indents_nested_infix_statements() {
+        assert_formats(
+            "@contains:history: | @thesaurus:automobile: & @thesaurus:motorcycle: & @thesaurus:bicycle: & @thesaurus:tram:",
+            &[
+                "@contains:history:",
+                "| @thesaurus:automobile: & @thesaurus:motorcycle: & @thesaurus:bicycle:",
+                "    & @thesaurus:tram:",
+            ],
+        );
+    }
+
+    #[test]
+    fn puts_parameters_on_their_own_lines() {
+        assert_formats(
+            "@weighted:automobile,0.2,motorcycle,0.2,bicycle,0.2,tram,0.2,locomotive,0.1,zeppelin,0.1:",
+            &[
+                "@weighted:",
+                "    automobile,0.2,",
+                "    motorcycle,0.2,",
+                "    bicycle,0.2,",
+                "    tram,0.2,",
+                "    locomotive,0.1,",
+                "    zeppelin,0.1:",
+            ],
+        );
+    }
+
+    #[test]
+    fn indents_negated_infix_statements() {
+        assert_formats(
+            "!(@thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle: | @thesaurus:tram:) & @contains:history:",
+            &[
+                "!(@thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle:",
+                "    | @thesaurus:tram:)",
+                "& @contains:history:",
+            ],
+        );
+    }
+
+    #[test]
+    fn formats_column_scopes() {
+        assert_formats(
+            "@in:Title:@thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle: | @contains:tram::",
+            &[
+                "@in:Title:",
+                "    @thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle:",
+                "    | @contains:tram:",
+                ":",
+            ],
+        );
+    }
+
+    #[test]
+    fn formats_macro_definitions() {
+        assert_formats(
+            "$vehicles = @thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle:; $vehicles & rust",
+            &[
+                "$vehicles =",
+                "    @thesaurus:automobile: | @thesaurus:motorcycle: | @thesaurus:bicycle:;",
+                "$vehicles & @contains:rust:",
+            ],
+        );
+    }
+
+    #[test]
+    fn prints_canonical_queries() {
+        for (query, expected) in [
+            ("@contains:-c:", "@contains:!c:"),
+            ("@near:a,b:", "@near:a,b,5:"),
+            ("rust go", "@contains:rust: & @contains:go:"),
+            (
+                "rust AND NOT go OR java",
+                "@contains:rust: & !@contains:go: | @contains:java:",
+            ),
+            (
+                "a & (b | c)",
+                "@contains:a: & (@contains:b: | @contains:c:)",
+            ),
+            ("title:rust", "@in:Title:@contains:rust::"),
+            ("@n:a,@sw:ru:,max,ordered:", "@near:a,\"ru*\",MAX,ordered:"),
+            (
+                "@lang:de: @sort:size,desc: rust~1",
+                "@lang:de: @sort:Size,desc: @fuzzy:rust,1:",
+            ),
+        ] {
+            assert_eq!(print(&parse_query(query)), expected, "{}", query);
+        }
+    }
+
+    // Printed queries parse back to the same ast for every syntax of the query language
+    #[test]
+    fn round_trips_every_syntax() {
+        for query in [
+            "@contains:rust & !go: | @contains:(a | b) & c:",
+            "!@contains:rust: & go",
+            "(@contains:a: | @contains:b:) & -c",
+            "rust | \"rust lang\" & ru*",
+            "1999-2000 war",
+            "@startswith:ru: | @inflection:run: | @thesaurus:car:",
+            "@near:a,b: & @near:a,b,max: & @near:a,@startswith:ru:,3,ordered:",
+            "@weighted:a,0.1,@thesaurus:car:,0.2,\"c d\",0.7:",
+            "@freetext:rust language,de:",
+            "@freetext:rock and -roll!:",
+            "@in:title:rust | go: & title:java",
+            "@lang:de: @sort:size,desc: rust",
+            "rust size>5 year<=2000 category=\"science fiction\"",
+            "rust category=\"a \\\"b\\\" \\\\\"",
+            "rust size:[1 TO 10] @range:modified,2000-01-01,2001-01-01:",
+            "rust~1 & @fuzzy:go,2:",
+            "$x = rust | go; $x & lang",
+        ] {
+            let ast = parse_query(query);
+            assert_eq!(parse_query(&print(&ast)), ast, "{}", query);
+            assert_eq!(parse_query(&format(&ast)), ast, "{}", query);
+        }
+    }
+}
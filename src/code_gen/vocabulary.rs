@@ -0,0 +1,132 @@
+use std::fs::read_to_string;
+use std::io::Error;
+
+use crate::code_gen::ast::{Expression, Operator, Statement};
+use crate::code_gen::lexer::edit_distance;
+use crate::code_gen::options::Options;
+
+// Expand the fuzzy terms of a query with the configured vocabulary
+// Without a vocabulary fuzzy terms are left as they are and the generator rejects them
+// Input: abstract syntax tree and options
+// Output: abstract syntax tree without fuzzy terms if there is a vocabulary
+pub fn expand_fuzzy(ast: Vec<Statement>, options: &Options) -> Vec<Statement> {
+    match &options.vocabulary {
+        Some(vocabulary) => vocabulary.expand(ast),
+        None => ast,
+    }
+}
+
+// Vocabulary of indexed words, MSSQL has no edit-distance matching so fuzzy terms are looked up in it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Vocabulary {
+    words: Vec<String>,
+}
+
+impl Vocabulary {
+    // Initial vocabulary creation
+    pub fn new(words: Vec<String>) -> Self {
+        Self { words }
+    }
+
+    // Read a vocabulary file, one word per line, empty lines are skipped
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let words = read_to_string(path)
+            .map_err(|error| {
+                Error::new(
+                    error.kind(),
+                    format!("Vocabulary {} cannot be read: {}", path, error),
+                )
+            })?
+            .lines()
+            .map(str::trim)
+            .filter(|word| !word.is_empty())
+            .map(str::to_owned)
+            .collect();
+        Ok(Self::new(words))
+    }
+
+    // Words of the vocabulary with an edit distance to the term of at most the given distance
+    // Input: term and maximal distance
+    // Output: vec of words, compared case-insensitive and without duplicates
+    pub fn close_spellings(&self, term: &str, distance: u64) -> Vec<String> {
+        let term = term.to_lowercase();
+        let mut spellings: Vec<String> = Vec::new();
+        for word in &self.words {
+            let word = word.to_lowercase();
+            if edit_distance(&term, &word) as u64 <= distance && !spellings.contains(&word) {
+                spellings.push(word);
+            }
+        }
+        spellings
+    }
+
+    // Words of the vocabulary starting with a prefix, used to complete terms
+    // Input: prefix
+    // Output: vec of words, compared case-insensitive and without duplicates
+    pub fn starting_with(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.to_lowercase();
+        let mut words: Vec<String> = Vec::new();
+        for word in &self.words {
+            let word = word.to_lowercase();
+            if word.starts_with(&prefix) && !words.contains(&word) {
+                words.push(word);
+            }
+        }
+        words
+    }
+
+    // Replace every fuzzy term by an OR of the term and its close spellings
+    // The spellings are combined as statements, so the generator encloses them in parentheses
+    pub fn expand(&self, ast: Vec<Statement>) -> Vec<Statement> {
+        ast.into_iter()
+            .map(|statement| self.expand_statement(statement))
+            .collect()
+    }
+
+    // Expand fuzzy terms in a statement and its nested statements
+    fn expand_statement(&self, statement: Statement) -> Statement {
+        match statement {
+            Statement::Fuzzy {
+                term: Expression::WordOrPhrase(term),
+                distance,
+            } => {
+                let spellings: Vec<String> = self
+                    .close_spellings(&term, distance)
+                    .into_iter()
+                    .filter(|spelling| !spelling.eq_ignore_ascii_case(&term))
+                    .collect();
+                let contains = |word: String| Statement::Contains {
+                    expression: Expression::WordOrPhrase(word),
+                };
+                spellings
+                    .into_iter()
+                    .fold(contains(term), |statement, spelling| Statement::Infix {
+                        statement: Box::new(statement),
+                        operator: Operator::Or,
+                        second_statement: Box::new(contains(spelling)),
+                    })
+            }
+            Statement::Infix {
+                statement,
+                operator,
+                second_statement,
+            } => Statement::Infix {
+                statement: Box::new(self.expand_statement(*statement)),
+                operator,
+                second_statement: Box::new(self.expand_statement(*second_statement)),
+            },
+            Statement::Prefix {
+                operator,
+                statement,
+            } => Statement::Prefix {
+                operator,
+                statement: Box::new(self.expand_statement(*statement)),
+            },
+            Statement::Column { columns, statement } => Statement::Column {
+                columns,
+                statement: Box::new(self.expand_statement(*statement)),
+            },
+            statement => statement,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use regex::Regex;
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::process::Command;
+
+// Default executor constants
+const SERVER: &str = "DESKTOP-JKNEH40\\SQLEXPRESS";
+const PATH_SQL: &str = "files\\fulltext.sql";
+const PATH_RESULTS: &str = "files\\results.txt";
+
+// Executor runs generated SQL statements with sqlcmd against a MSSQL Server
+// The statement and the result are exchanged through files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Executor {
+    pub server: String,
+    pub sql_path: String,
+    pub results_path: String,
+}
+
+// Default executor uses the local MSSQL Server
+impl Default for Executor {
+    fn default() -> Self {
+        Self {
+            server: SERVER.to_owned(),
+            sql_path: PATH_SQL.to_owned(),
+            results_path: PATH_RESULTS.to_owned(),
+        }
+    }
+}
+
+impl Executor {
+    // Write an sql statement to the input file, execute it and read the results
+    // Input: sql statement
+    // Output: vec of titles and their search rank, none if the result cannot be read
+    pub fn run(&self, sql: &str) -> std::io::Result<Option<Vec<(String, u64)>>> {
+        write!(File::create(&self.sql_path)?, "{}", sql)?;
+        self.execute_sql()?;
+        Ok(read_results(&self.results_path))
+    }
+
+    // Runs a command to execute the sql statement in the input file
+    // Output: txt file interpretation of the MSSQL Server result
+    pub fn execute_sql(&self) -> std::io::Result<()> {
+        Command::new("cmd")
+            .args([
+                "/C",
+                "sqlcmd",
+                "-S",
+                &self.server,
+                "-i",
+                &self.sql_path,
+                "-o",
+                &self.results_path,
+            ])
+            .output()?;
+        Ok(())
+    }
+}
+
+// Reads the txt file result and extracts the actual results
+// Input: path to the txt file
+// Output: vec of titles and their search rank
+pub fn read_results(path: &str) -> Option<Vec<(String, u64)>> {
+    let contents = read_to_string(path).ok()?;
+    let mut contents_vec: Vec<&str> = contents.split('\n').collect();
+    // In case of error message, break
+    if contents_vec.len() < 6 {
+        return None;
+    }
+    // Remove metadata rows
+    // First 3-4 rows and last three rows
+    while !contents_vec[0].starts_with("---") {
+        contents_vec.remove(0);
+    }
+    contents_vec.remove(0);
+    contents_vec.remove(contents_vec.len() - 1);
+    contents_vec.remove(contents_vec.len() - 1);
+    contents_vec.remove(contents_vec.len() - 1);
+    // Go through each row and extract the titles and their ranks
+    let mut results: Vec<(String, u64)> = Vec::new();
+    let re = Regex::new(r"\s+").unwrap();
+    for row in contents_vec {
+        // Remove unnecessary whitespaces
+        let row = row.replace('\r', "");
+        let row = re.replace_all(&row, " ").to_string();
+        // Extract last 'word' as rank and save the rest as the title
+        let mut words: Vec<&str> = row.split(' ').collect();
+        let rank = words[words.len() - 1].parse::<u64>().ok()?;
+        words.remove(words.len() - 1);
+        let title = words.join(" ");
+
+        results.push((title, rank));
+    }
+    Some(results)
+}
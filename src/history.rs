@@ -0,0 +1,136 @@
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+// Default history constants
+const ENTRIES: usize = 10;
+const SESSIONS: usize = 1000;
+
+// Past search of a session with the options it was run with
+// Results is the number of results, none if the search failed or its results cannot be read
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub search: String,
+    pub lang: Option<String>,
+    pub sort: Option<String>,
+    pub results: Option<usize>,
+    pub error: Option<String>,
+}
+
+// Server-side search history, every session keeps a ring buffer of its latest searches
+// The oldest session is dropped when there are too many sessions
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: usize,
+    sessions: usize,
+    histories: HashMap<String, VecDeque<HistoryEntry>>,
+    order: VecDeque<String>,
+}
+
+// Default history keeps 10 searches for each of 1000 sessions
+impl Default for History {
+    fn default() -> Self {
+        Self::new(ENTRIES, SESSIONS)
+    }
+}
+
+impl History {
+    pub fn new(entries: usize, sessions: usize) -> Self {
+        Self {
+            entries,
+            sessions,
+            histories: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    // Start a new session with an empty history
+    // Output: session id of 128 bits from the random source of the operating system, so it cannot be guessed
+    pub fn start_session(&mut self) -> String {
+        let mut id = String::new();
+        while id.is_empty() || self.histories.contains_key(&id) {
+            let mut bytes = [0u8; 16];
+            getrandom::getrandom(&mut bytes).expect("operating system provides no random source");
+            id = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        }
+        if self.order.len() >= self.sessions {
+            if let Some(oldest) = self.order.pop_front() {
+                self.histories.remove(&oldest);
+            }
+        }
+        self.histories.insert(id.clone(), VecDeque::new());
+        self.order.push_back(id.clone());
+        id
+    }
+
+    // Check if a session id from a cookie belongs to a running session
+    pub fn has_session(&self, id: &str) -> bool {
+        self.histories.contains_key(id)
+    }
+
+    // Add a search to the history of a session, the oldest search is dropped if the history is full
+    pub fn record(&mut self, id: &str, entry: HistoryEntry) {
+        if let Some(history) = self.histories.get_mut(id) {
+            if history.len() >= self.entries {
+                history.pop_back();
+            }
+            history.push_front(entry);
+        }
+    }
+
+    // Searches of a session, latest search first
+    pub fn entries(&self, id: &str) -> Vec<HistoryEntry> {
+        self.histories
+            .get(id)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(search: &str) -> HistoryEntry {
+        HistoryEntry {
+            search: search.to_owned(),
+            lang: None,
+            sort: None,
+            results: Some(1),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn keeps_latest_searches_first() {
+        let mut history = History::new(2, 10);
+        let id = history.start_session();
+        for search in ["rust", "go", "zig"] {
+            history.record(&id, entry(search));
+        }
+        assert_eq!(history.entries(&id), vec![entry("zig"), entry("go")]);
+    }
+
+    #[test]
+    fn separates_sessions() {
+        let mut history = History::default();
+        let first = history.start_session();
+        let second = history.start_session();
+        assert_ne!(first, second);
+        history.record(&first, entry("rust"));
+        assert_eq!(history.entries(&first), vec![entry("rust")]);
+        assert!(history.entries(&second).is_empty());
+    }
+
+    #[test]
+    fn drops_oldest_session() {
+        let mut history = History::new(10, 2);
+        let oldest = history.start_session();
+        let kept = history.start_session();
+        let latest = history.start_session();
+        assert!(!history.has_session(&oldest));
+        assert!(history.has_session(&kept) && history.has_session(&latest));
+        // Searches of unknown sessions are not recorded
+        history.record(&oldest, entry("rust"));
+        assert!(history.entries(&oldest).is_empty());
+    }
+}
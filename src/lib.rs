@@ -0,0 +1,99 @@
+use thiserror::Error;
+
+mod code_gen;
+pub mod executor;
+pub mod history;
+pub mod saved;
+
+pub use code_gen::options::{Backend, FieldType, Options, Order};
+pub use code_gen::{
+    ast, completion, generator, lexer, macros, options, parser, printer, vocabulary,
+};
+
+use std::ops::Range;
+
+use ast::Statement;
+use generator::GenerateError;
+use lexer::Token;
+use macros::MacroError;
+use parser::ParseError;
+
+// Main function of the query compiler, runs lexer, parser and generator one after another
+// Input: search string and options describing the database
+// Output: string (sql statement) or error of the failing step
+pub fn compile(query: &str, options: &Options) -> Result<String, CompileError> {
+    compile_staged(query, options).map(|compilation| compilation.sql)
+}
+
+// Compilation process which keeps the result of every step to show how a query is understood
+// Macros and fuzzy terms are expanded before generation, the kept ast is the one before expansion
+// Input: search string and options describing the database
+// Output: tokens, ast and sql statement or error of the failing step
+pub fn compile_staged(query: &str, options: &Options) -> Result<Compilation, CompileError> {
+    let tokens = lexer::lex_spanned(query, options);
+    let ast = parser::parse_with_position(
+        tokens.iter().map(|(token, _)| token.clone()).collect(),
+        options,
+    )
+    .map_err(|(error, position)| CompileError::Parse {
+        error,
+        // Errors at the end of the input point right behind the last character
+        span: tokens
+            .get(position)
+            .map_or(query.len()..query.len() + 1, |(_, span)| span.clone()),
+    })?;
+    let expanded = macros::expand_macros(ast.clone())?;
+    let expanded = vocabulary::expand_fuzzy(expanded, options);
+    let sql = generator::generate(expanded, options)?;
+    Ok(Compilation { tokens, ast, sql })
+}
+
+// Results of the compilation steps, tokens with their byte ranges in the query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compilation {
+    pub tokens: Vec<(Token, Range<usize>)>,
+    pub ast: Vec<Statement>,
+    pub sql: String,
+}
+
+// Types of errors covered by the compiler, parse errors keep the byte range of the failing token
+#[derive(Debug, Error)]
+pub enum CompileError {
+    #[error("{error}")]
+    Parse {
+        error: ParseError,
+        span: Range<usize>,
+    },
+    #[error("{0}")]
+    Macro(#[from] MacroError),
+    #[error("{0}")]
+    Generate(#[from] GenerateError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_tokens_and_ast_before_expansion() {
+        let compilation = compile_staged("$x = rust; $x", &Options::default()).unwrap();
+        assert_eq!(compilation.tokens[0], (Token::Macro("x".to_owned()), 0..2));
+        assert!(matches!(compilation.ast[0], Statement::Define { .. }));
+        assert_eq!(
+            compilation.sql,
+            compile("rust", &Options::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn points_parse_errors_at_the_failing_token() {
+        match compile("@contains:rust: & ) @contains:go:", &Options::default()) {
+            Err(CompileError::Parse { span, .. }) => assert_eq!(span, 18..19),
+            other => panic!("unexpected {:?}", other),
+        }
+        match compile("@contains:rust: &", &Options::default()) {
+            Err(CompileError::Parse { span, .. }) => assert_eq!(span, 17..18),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::lexer::{self, Token};
+use crate::parser::{self, ParseError};
+use crate::Options;
+
+// Placeholders are written as {name} inside a saved query
+const PLACEHOLDER: &str = r"\{([a-zA-Z_]+)\}";
+// Values a placeholder is replaced with for validation, tried in order until the query parses
+// The name fits where a term stands, the others fit number and date positions
+const SUBSTITUTES: [&str; 2] = ["1", "2000-01-01"];
+
+// Saved search with a name, a query and the names of the placeholders in the query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+    pub parameters: Vec<String>,
+}
+
+impl SavedSearch {
+    // Create a saved search, the query is validated with the parser
+    // Placeholders are replaced by their own name for validation
+    // If the parser fails at a placeholder, it is replaced by a number and then a date instead
+    // Input: name and query with placeholders
+    // Output: saved search or error
+    pub fn new(name: &str, query: &str) -> Result<Self, SavedError> {
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(SavedError::InvalidName(name.to_owned()));
+        }
+        let mut parameters: Vec<String> = Vec::new();
+        for captures in placeholder().captures_iter(query) {
+            let parameter = captures[1].to_owned();
+            if !parameters.contains(&parameter) {
+                parameters.push(parameter);
+            }
+        }
+        let mut substitutes: HashMap<&str, usize> = HashMap::new();
+        loop {
+            let (validated, placeholders) = substitute(query, &substitutes);
+            let (error, span) = match parse(&validated) {
+                Ok(()) => break,
+                Err(error) => error,
+            };
+            // Only the placeholder the parser failed at gets the next substitute
+            let failed = placeholders
+                .into_iter()
+                .find(|(_, range)| range.start < span.end && span.start < range.end)
+                .map(|(parameter, _)| parameter)
+                .filter(|parameter| {
+                    substitutes.get(parameter).copied().unwrap_or(0) < SUBSTITUTES.len()
+                });
+            match failed {
+                Some(parameter) => *substitutes.entry(parameter).or_insert(0) += 1,
+                None => return Err(SavedError::Invalid(name.to_owned(), error)),
+            }
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            query: query.trim().to_owned(),
+            parameters,
+        })
+    }
+
+    // Replace the placeholders with bound values
+    // Every value has to be a single term or number, so values cannot change the structure of the query
+    // The bound query is parsed again, as a value may not fit the position of its placeholder
+    // Input: values by parameter name
+    // Output: query without placeholders or error
+    pub fn bind(&self, values: &HashMap<String, String>) -> Result<String, SavedError> {
+        if let Some(unknown) = values.keys().find(|name| !self.parameters.contains(name)) {
+            return Err(SavedError::UnknownParameter(
+                self.name.clone(),
+                unknown.clone(),
+            ));
+        }
+        for parameter in &self.parameters {
+            let value = values
+                .get(parameter)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| SavedError::MissingValue(parameter.clone()))?;
+            match lexer::lex(value).as_slice() {
+                [Token::WordOrPhrase(_)]
+                | [Token::Date(_)]
+                | [Token::Number(_)]
+                | [Token::ZeroToOne(_)] => {}
+                _ => {
+                    return Err(SavedError::InvalidValue(
+                        parameter.clone(),
+                        value.to_owned(),
+                    ))
+                }
+            }
+        }
+        let bound = placeholder().replace_all(&self.query, |captures: &Captures| {
+            values[&captures[1]].trim().to_owned()
+        });
+        parse(&bound).map_err(|(error, _)| SavedError::Unbound(self.name.clone(), error))?;
+        Ok(bound.into_owned())
+    }
+}
+
+// File-backed store of saved searches, every change is written to the file immediately
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSearches {
+    path: String,
+    searches: Vec<SavedSearch>,
+}
+
+impl SavedSearches {
+    // Load the saved searches from a json file, a missing file is an empty store
+    pub fn load(path: &str) -> Result<Self, SavedError> {
+        let searches = if Path::new(path).exists() {
+            serde_json::from_str(&read_to_string(path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_owned(),
+            searches,
+        })
+    }
+
+    pub fn list(&self) -> &[SavedSearch] {
+        &self.searches
+    }
+
+    pub fn get(&self, name: &str) -> Result<&SavedSearch, SavedError> {
+        self.searches
+            .iter()
+            .find(|search| search.name == name)
+            .ok_or_else(|| SavedError::UnknownSearch(name.to_owned()))
+    }
+
+    // Save a new search or update the query of an existing one
+    // Input: name and query with placeholders
+    // Output: validated saved search or error, the store is unchanged on error
+    pub fn save(&mut self, name: &str, query: &str) -> Result<SavedSearch, SavedError> {
+        let saved = SavedSearch::new(name, query)?;
+        match self
+            .searches
+            .iter_mut()
+            .find(|search| search.name == saved.name)
+        {
+            Some(search) => *search = saved.clone(),
+            None => self.searches.push(saved.clone()),
+        }
+        self.write()?;
+        Ok(saved)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), SavedError> {
+        let position = self
+            .searches
+            .iter()
+            .position(|search| search.name == name)
+            .ok_or_else(|| SavedError::UnknownSearch(name.to_owned()))?;
+        self.searches.remove(position);
+        self.write()
+    }
+
+    fn write(&self) -> Result<(), SavedError> {
+        let json = serde_json::to_string_pretty(&self.searches)?;
+        write!(File::create(&self.path)?, "{}", json)?;
+        Ok(())
+    }
+}
+
+fn placeholder() -> Regex {
+    Regex::new(PLACEHOLDER).unwrap()
+}
+
+// Replace every placeholder by its own name or the selected substitute
+// Input: query with placeholders and index of the substitute by parameter, shifted by one for the name
+// Output: query without placeholders and the byte range of every replaced placeholder
+fn substitute<'q>(
+    query: &'q str,
+    substitutes: &HashMap<&str, usize>,
+) -> (String, Vec<(&'q str, Range<usize>)>) {
+    let mut replaced = String::new();
+    let mut placeholders = Vec::new();
+    let mut last = 0;
+    for captures in placeholder().captures_iter(query) {
+        let (whole, parameter) = (captures.get(0).unwrap(), captures.get(1).unwrap());
+        replaced.push_str(&query[last..whole.start()]);
+        let start = replaced.len();
+        match substitutes.get(parameter.as_str()) {
+            Some(index) if *index > 0 => replaced.push_str(SUBSTITUTES[index - 1]),
+            _ => replaced.push_str(parameter.as_str()),
+        }
+        placeholders.push((parameter.as_str(), start..replaced.len()));
+        last = whole.end();
+    }
+    replaced.push_str(&query[last..]);
+    (replaced, placeholders)
+}
+
+// Parse a query without placeholders
+// Output: nothing or the error with the byte range of the failing token
+fn parse(query: &str) -> Result<(), (ParseError, Range<usize>)> {
+    let options = Options::default();
+    let tokens = lexer::lex_spanned(query, &options);
+    parser::parse_with_position(
+        tokens.iter().map(|(token, _)| token.clone()).collect(),
+        &options,
+    )
+    .map(|_| ())
+    .map_err(|(error, position)| {
+        // Errors at the end of the input point right behind the last character
+        let span = tokens
+            .get(position)
+            .map_or(query.len()..query.len() + 1, |(_, span)| span.clone());
+        (error, span)
+    })
+}
+
+// Types of errors covered by saved searches
+#[derive(Debug, Error)]
+pub enum SavedError {
+    #[error("Invalid name '{0}', only letters, digits, _ and - are allowed.")]
+    InvalidName(String),
+    #[error("Saved search {0} is invalid: {1}")]
+    Invalid(String, ParseError),
+    #[error("Unknown saved search {0}.")]
+    UnknownSearch(String),
+    #[error("Saved search {0} has no parameter {1}.")]
+    UnknownParameter(String, String),
+    #[error("Missing value for parameter {0}.")]
+    MissingValue(String),
+    #[error("Value '{1}' for parameter {0} is not a single term.")]
+    InvalidValue(String, String),
+    #[error("Values do not fit saved search {0}: {1}")]
+    Unbound(String, ParseError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("Saved searches cannot be read: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn collects_parameters_once() {
+        let saved = SavedSearch::new("lang", "{lang} & ({lang} | {other})").unwrap();
+        assert_eq!(saved.parameters, vec!["lang", "other"]);
+    }
+
+    #[test]
+    fn rejects_invalid_names_and_queries() {
+        assert!(matches!(
+            SavedSearch::new("no space", "rust"),
+            Err(SavedError::InvalidName(_))
+        ));
+        assert!(matches!(
+            SavedSearch::new("broken", "{lang} & "),
+            Err(SavedError::Invalid(..))
+        ));
+        assert!(matches!(
+            SavedSearch::new("broken", "rust size:[{min} TO big]"),
+            Err(SavedError::Invalid(..))
+        ));
+    }
+
+    #[test]
+    fn validates_placeholders_in_number_and_date_positions() {
+        assert!(SavedSearch::new("sized", "rust size:[{min} TO {max}]").is_ok());
+        assert!(SavedSearch::new("near", "@near:rust,go,{dist}:").is_ok());
+        assert!(SavedSearch::new("dated", "rust modified:[{from} TO 2020-01-01]").is_ok());
+    }
+
+    #[test]
+    fn binds_terms_numbers_and_dates() {
+        let saved = SavedSearch::new("sized", "{lang} size:[{min} TO {max}]").unwrap();
+        assert_eq!(
+            saved
+                .bind(&values(&[("lang", "rust"), ("min", "1"), ("max", "1000")]))
+                .unwrap(),
+            "rust size:[1 TO 1000]"
+        );
+        let saved = SavedSearch::new("near", "@near:rust,go,{dist}:").unwrap();
+        assert_eq!(
+            saved.bind(&values(&[("dist", "5")])).unwrap(),
+            "@near:rust,go,5:"
+        );
+        let saved = SavedSearch::new("dated", "rust modified:[{from} TO 2020-01-01]").unwrap();
+        assert_eq!(
+            saved.bind(&values(&[("from", "2019-02-28")])).unwrap(),
+            "rust modified:[2019-02-28 TO 2020-01-01]"
+        );
+    }
+
+    #[test]
+    fn rejects_values_which_do_not_fit() {
+        let saved = SavedSearch::new("sized", "{lang} size:[{min} TO 10]").unwrap();
+        assert!(matches!(
+            saved.bind(&values(&[("lang", "rust"), ("min", "small")])),
+            Err(SavedError::Unbound(..))
+        ));
+        assert!(matches!(
+            saved.bind(&values(&[("lang", "rust | go"), ("min", "1")])),
+            Err(SavedError::InvalidValue(..))
+        ));
+        assert!(matches!(
+            saved.bind(&values(&[("lang", "rust")])),
+            Err(SavedError::MissingValue(_))
+        ));
+        assert!(matches!(
+            saved.bind(&values(&[("lang", "rust"), ("min", "1"), ("max", "2")])),
+            Err(SavedError::UnknownParameter(..))
+        ));
+    }
+}